@@ -0,0 +1,79 @@
+//! Full games on `Minesweeper` against the same games on `BitBoard`.
+//!
+//! Run with `cargo bench --bench engines`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use minesweeper::{
+    board::Board,
+    grid::Coord,
+    minesweeper::{Difficulty, MinesweeperBuilder},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+// opens the centre, then random hidden cells until the game is over
+fn play_random<B: Board>(board: &mut B, rng: &mut StdRng) -> bool {
+    let (width, height) = (board.width(), board.height());
+    let _ = board.open(width / 2, height / 2);
+    while board.is_playing() {
+        let (x, y) = (rng.gen_range(0..width), rng.gen_range(0..height));
+        if !board.cell(Coord::new(x, y)).revealed {
+            let _ = board.open(x, y);
+        }
+    }
+    board.is_won()
+}
+
+fn full_games(c: &mut Criterion) {
+    for difficulty in Difficulty::PRESETS {
+        let mut group = c.benchmark_group(format!("{}", difficulty));
+        let builder = difficulty.builder();
+
+        group.bench_function(BenchmarkId::new("Minesweeper", "build and play"), |b| {
+            let mut rng = StdRng::seed_from_u64(0);
+            b.iter(|| {
+                let mut ms = builder.seed(rng.gen()).build().unwrap();
+                black_box(play_random(&mut ms, &mut rng))
+            })
+        });
+        group.bench_function(BenchmarkId::new("BitBoard", "build and play"), |b| {
+            let mut rng = StdRng::seed_from_u64(0);
+            b.iter(|| {
+                let mut board = builder.seed(rng.gen()).build_bitboard().unwrap();
+                black_box(play_random(&mut board, &mut rng))
+            })
+        });
+        group.bench_function(BenchmarkId::new("BitBoard", "restart and play"), |b| {
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut board = builder.build_bitboard().unwrap();
+            b.iter(|| {
+                board.restart(rng.gen());
+                black_box(play_random(&mut board, &mut rng))
+            })
+        });
+        group.finish();
+    }
+}
+
+fn first_click(c: &mut Criterion) {
+    // one flood fill over almost the whole board
+    let mut group = c.benchmark_group("1000x1000 first click");
+    group.sample_size(10);
+    let builder = MinesweeperBuilder::new(1000, 1000, 1000).seed(1);
+
+    group.bench_function("Minesweeper", |b| {
+        b.iter(|| {
+            let mut ms = builder.build().unwrap();
+            black_box(ms.open(500, 500).unwrap())
+        })
+    });
+    group.bench_function("BitBoard", |b| {
+        b.iter(|| {
+            let mut board = builder.build_bitboard().unwrap();
+            black_box(board.open(500, 500).unwrap())
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, full_games, first_click);
+criterion_main!(benches);
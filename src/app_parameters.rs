@@ -1,3 +1,5 @@
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
 pub use bevy::{
     prelude::*,
     render::{render_resource::SamplerDescriptor, texture::ImageSampler},
@@ -5,16 +7,35 @@ pub use bevy::{
 };
 
 use bevy::{
+    app::AppExit,
     sprite::{
         collide_aabb::{collide, Collision},
         Anchor,
     },
+    tasks::{AsyncComputeTaskPool, Task},
     window::PrimaryWindow,
 };
+use futures_lite::future;
 
-use std::{cmp::max, collections::HashMap, path::Path};
+use std::{
+    cmp::max,
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use crate::minesweeper::*;
+use crate::{
+    grid::{Coord, Grid},
+    leaderboard::{Entry, Leaderboard, TOP},
+    metrics::game_stats,
+    minesweeper::*,
+    probability::mine_probabilities,
+    replay::{self, Replay, ReplayError},
+    save::Format,
+    solver::hint,
+};
 
 const INTRO_FONT_SIZE: f32 = 60.0;
 const INPUT_TEXT_FONT_SIZE: f32 = 120.0;
@@ -23,11 +44,48 @@ const NORMAL_BUTTON: Color = Color::rgb(0.7, 0.7, 0.7);
 const HOVERED_BUTTON: Color = Color::rgb(0.8, 0.8, 0.8);
 const PRESSED_BUTTON: Color = Color::rgb(0.3, 0.3, 0.3);
 
+// grids the solver may play through for a no guess game, fewer on bigger
+// grids where every attempt takes longer
+const NO_GUESS_WORK: usize = 1_000_000;
+const NO_GUESS_ATTEMPTS: (usize, usize) = (10, 10_000);
+
+// seconds a toast is shown, the last of them fading out
+const TOAST_TIME: f32 = 4.;
+const TOAST_FADE: f32 = 1.;
+
 #[derive(Resource, Clone, Copy, Default, Debug)]
 pub struct MSInfo {
-    width: usize,
-    height: usize,
-    mines: usize,
+    difficulty: Difficulty,
+    // seed of the current game, kept to replay the same board
+    seed: Option<u64>,
+    no_guess: bool,
+    practice: bool,
+}
+
+impl MSInfo {
+    fn from_game(ms: &Minesweeper) -> Self {
+        MSInfo {
+            difficulty: ms.difficulty(),
+            seed: Some(ms.seed),
+            no_guess: ms.generation() != GenerationPolicy::Random,
+            practice: ms.practice(),
+        }
+    }
+
+    fn builder(&self) -> MinesweeperBuilder {
+        let mut builder = self.difficulty.builder();
+        if self.no_guess {
+            let (width, height, _) = self.difficulty.size();
+            let (least, most) = NO_GUESS_ATTEMPTS;
+            let attempts = (NO_GUESS_WORK / (width * height)).clamp(least, most);
+            builder = builder.generation(GenerationPolicy::NoGuess { attempts });
+        }
+        builder = builder.practice(self.practice);
+        match self.seed {
+            Some(seed) => builder.seed(seed),
+            None => builder,
+        }
+    }
 }
 
 #[derive(Resource)]
@@ -41,11 +99,364 @@ pub struct GameWon {
     value: bool, 
 }
 
+#[derive(Resource)]
+pub struct Game {
+    ms: Minesweeper,
+    // the game was continued and must not be generated again
+    resumed: bool,
+    // every input of the player, to watch the game again
+    replay: Replay,
+    // `Time::elapsed` when the first cell was revealed
+    start: Option<Duration>,
+    // time on the clock, stops when the game is over
+    elapsed: Duration,
+    // a no guess grid generated away from the frame, with the input that started it
+    // and whether it was applied
+    generating: Option<Task<(Minesweeper, Replay, bool)>>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        let ms = Minesweeper::default();
+        Game {
+            replay: Replay::new(&ms),
+            ms,
+            resumed: false,
+            start: None,
+            elapsed: Duration::ZERO,
+            generating: None,
+        }
+    }
+}
+
+impl Game {
+    // hints are recorded like inputs, so a continued game still has them
+    fn hints(&self) -> usize {
+        self.replay.records.iter().filter(|r| r.input == replay::Input::Hint).count()
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Scores {
+    value: Leaderboard,
+}
+
+// name put on new records, typed on the end screen
+#[derive(Resource)]
+pub struct PlayerName {
+    value: String,
+}
+
+impl PlayerName {
+    const MAX_LEN: usize = 16;
+}
+
+impl Default for PlayerName {
+    fn default() -> Self {
+        PlayerName {
+            value: std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .unwrap_or_else(|_| "Player".to_owned()),
+        }
+    }
+}
+
+// game left unfinished on the last exit, taken by "Continue"
+#[derive(Resource, Default)]
+pub struct SavedGame {
+    value: Option<Minesweeper>,
+    replay: Option<Replay>,
+}
+
+// recorded game shown in `GameState::Replay`
+#[derive(Resource)]
+pub struct Viewer {
+    replay: Replay,
+    ms: Minesweeper,
+    // number of inputs already applied to `ms`
+    position: usize,
+    playing: bool,
+    speed: f32,
+    // time of the replay, not of the app
+    clock: Duration,
+}
+
+impl Viewer {
+    fn new(replay: Replay) -> Result<Self, ConfigError> {
+        Ok(Viewer {
+            ms: replay.game()?,
+            replay,
+            position: 0,
+            playing: true,
+            speed: 1.,
+            clock: Duration::ZERO,
+        })
+    }
+
+    // the error of a broken input, the replay ends before it
+    fn step_forward(&mut self) -> Result<(), ReplayError> {
+        let Some(record) = self.replay.records.get(self.position) else {
+            self.playing = false;
+            return Ok(());
+        };
+        let time = record.time;
+        if let Err(e) = self.replay.apply(&mut self.ms, self.position) {
+            // the rest of a broken replay can't be shown
+            self.replay.records.truncate(self.position);
+            self.ms = self.replay.play().expect("these inputs were applied before");
+            return Err(e);
+        }
+        self.clock = time;
+        self.position += 1;
+        Ok(())
+    }
+
+    fn step_back(&mut self) {
+        if self.position == 0 {
+            return;
+        }
+        self.position -= 1;
+        // inputs only go forward, so the game is played again up to the new position
+        self.ms = self.replay.play_to(self.position).expect("these inputs were applied before");
+        self.clock = match self.position {
+            0 => Duration::ZERO,
+            p => self.replay.records[p - 1].time,
+        };
+    }
+
+    // cell of the last action, undo and redo keep the marker where it was
+    fn cursor(&self) -> Option<(usize, usize)> {
+        self.replay.records[..self.position].iter().rev().find_map(|record| match record.input {
+            replay::Input::Action(action) => Some(action.position()),
+            _ => None,
+        })
+    }
+}
+
+fn data_path(file: &str) -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("minesweeper").join(file))
+}
+
+fn create_data_file(path: &Path) -> io::Result<BufWriter<File>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    Ok(BufWriter::new(File::create(path)?))
+}
+
+fn cell_image(cell: Cell) -> String {
+    match (cell.revealed, cell.mine, cell.flag) {
+        (true, true, _) => "mine".to_owned(),
+        (true, false, _) => cell.surrounds.to_string(),
+        (false, _, true) => "flag".to_owned(),
+        (false, _, false) => "cell".to_owned(),
+    }
+}
+
+fn spawn_cells(c: &mut Commands, gr: &GameRes, width: usize, height: usize) {
+    for _ in 0..width * height {
+        c.spawn(SpriteBundle {
+            texture: gr.imgs.get("cell").unwrap().clone(),
+            sprite: Sprite {
+                color: Color::Rgba{red: 1., green: 1., blue: 1., alpha: 1.},
+                custom_size: Some(Vec2::new(1., 1.)),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(MS);
+    }
+}
+
 #[derive(Component)]
 pub struct MS;
 
+// shows why the intro fields can't start a game
+#[derive(Component)]
+pub struct FieldError;
+
+#[derive(Component)]
+pub struct Menu;
+
+#[derive(Component)]
+pub struct ReplayButton;
+
+#[derive(Component)]
+pub struct ContinueButton;
+
 #[derive(Component)]
-pub struct InputText;
+pub struct WatchButton;
+
+#[derive(Component)]
+pub struct LeaderboardButton;
+
+#[derive(Component)]
+pub struct LeaderboardText;
+
+// end screen text with a new record, its name is edited in the sections after
+// the table
+#[derive(Component)]
+pub struct RecordName {
+    place: usize,
+    table: usize,
+}
+
+// marks the cell of the last action while watching a replay
+#[derive(Component)]
+pub struct ReplayCursor;
+
+#[derive(Component)]
+pub struct ReplayStatus;
+
+// timer and mine counter shown while playing
+#[derive(Component)]
+pub struct Hud;
+
+/// A message shown on top of the window for a few seconds, on every screen
+#[derive(Clone, Debug)]
+pub struct Toast {
+    pub message: String,
+    pub error: bool,
+}
+
+impl Toast {
+    pub fn info(message: impl Into<String>) -> Self {
+        Toast { message: message.into(), error: false }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Toast { message: message.into(), error: true }
+    }
+}
+
+// column of the shown toasts, newest at the bottom
+#[derive(Component)]
+pub struct ToastList;
+
+// a shown toast, despawned when its timer finishes
+#[derive(Component)]
+pub struct ToastText {
+    timer: Timer,
+}
+
+// overlays drawn over the grid while playing
+#[derive(Default)]
+pub struct Overlay {
+    heatmap: Option<Grid<Option<f64>>>,
+    hinted: Option<Coord>,
+}
+
+// on/off options of the intro screen, put on the button and its text
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Toggle {
+    NoGuess,
+    Practice,
+}
+
+impl Toggle {
+    fn label(&self, ms_info: &MSInfo) -> String {
+        let (name, on) = match self {
+            Toggle::NoGuess => ("No guessing", ms_info.no_guess),
+            Toggle::Practice => ("Practice", ms_info.practice),
+        };
+        match on {
+            true => format!("{}: on", name),
+            false => format!("{}: off", name),
+        }
+    }
+
+    fn flip(&self, ms_info: &mut MSInfo) {
+        match self {
+            Toggle::NoGuess => ms_info.no_guess = !ms_info.no_guess,
+            Toggle::Practice => ms_info.practice = !ms_info.practice,
+        }
+    }
+}
+
+// input fields of the intro screen, put on the button and its text
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Field {
+    #[default]
+    Width,
+    Height,
+    Mines,
+}
+
+impl Field {
+    const ALL: [Field; 3] = [Field::Width, Field::Height, Field::Mines];
+    const MAX_DIGITS: usize = 3;
+
+    fn label(&self) -> &'static str {
+        match self {
+            Field::Width => "Width: ",
+            Field::Height => "Height: ",
+            Field::Mines => "Mines: ",
+        }
+    }
+}
+
+// difficulty buttons of the intro screen, `None` opens the custom fields
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Preset(Option<Difficulty>);
+
+impl Preset {
+    const ALL: [Preset; 4] = [
+        Preset(Some(Difficulty::Beginner)),
+        Preset(Some(Difficulty::Intermediate)),
+        Preset(Some(Difficulty::Expert)),
+        Preset(None),
+    ];
+
+    fn label(&self) -> String {
+        match self.0 {
+            Some(difficulty) => difficulty.to_string(),
+            None => "Custom".to_owned(),
+        }
+    }
+}
+
+// the row holding the custom fields, hidden while a preset is chosen
+#[derive(Component)]
+pub struct FieldRow;
+
+// typed values of the intro fields, indexed by `Field`
+#[derive(Resource, Default)]
+pub struct Fields {
+    values: [String; 3],
+    focus: Field,
+    // the fields are used instead of the preset in `MSInfo`
+    custom: bool,
+}
+
+impl Fields {
+    fn new(ms_info: &MSInfo) -> Self {
+        let (width, height, mines) = ms_info.difficulty.size();
+        Fields {
+            values: [width, height, mines].map(|v| v.to_string()),
+            focus: Field::Width,
+            custom: matches!(ms_info.difficulty, Difficulty::Custom { .. }),
+        }
+    }
+
+    // the game the fields describe, or the field to fix and why
+    fn info(&self, ms_info: &MSInfo) -> Result<MSInfo, (Field, String)> {
+        let parse = |field: Field| {
+            self.values[field as usize].parse::<usize>().map_err(|_| (field, "Enter a number".to_owned()))
+        };
+        let info = MSInfo {
+            difficulty: Difficulty::from_size(parse(Field::Width)?, parse(Field::Height)?, parse(Field::Mines)?),
+            seed: None,
+            ..*ms_info
+        };
+
+        match info.builder().validate() {
+            Ok(()) => Ok(info),
+            Err(e @ ConfigError::EmptyGrid { width: 0, .. }) => Err((Field::Width, e.to_string())),
+            Err(e @ ConfigError::EmptyGrid { .. }) => Err((Field::Height, e.to_string())),
+            Err(e) => Err((Field::Mines, e.to_string())),
+        }
+    }
+}
 
 #[derive(Clone, PartialEq, Eq, Debug, Hash, States, Default)]
 pub enum GameState {
@@ -53,11 +464,13 @@ pub enum GameState {
     Intro,
     Playing,
     Endgame,
+    Replay,
 }
 
 pub fn startup(
     a: Res<AssetServer>,
     mut c: Commands,
+    mut toasts: EventWriter<Toast>,
 ) {
     c.spawn(Camera2dBundle::default());
 
@@ -77,168 +490,594 @@ pub fn startup(
         font: a.load(Path::new("fonts").join("Nunito-Regular.ttf")),
         imgs
     });
-}
 
-pub fn init(
-    gr: Res<GameRes>,
-    mut c: Commands,
-) {
-    // spawn starting text
-    c.spawn(Text2dBundle {
-        text: Text {
-            sections: vec![TextSection {
-                value: "Size of Minesweeper grid:".to_owned(),
-                style: TextStyle {
-                    font: gr.font.clone(),
-                    font_size: INTRO_FONT_SIZE,
-                    color: Color::rgb(0.9, 0.9, 0.9),
-                },
-            }],
-            ..default()
+    // load the game left on the last exit
+    let saved = match data_path("game.sav").map(File::open) {
+        Some(Ok(file)) => match Minesweeper::load(BufReader::new(file), Format::Binary) {
+            Ok(ms) => Some(ms),
+            Err(e) => {
+                toasts.send(Toast::error(format!("Could not load the saved game: {}", e)));
+                None
+            }
         },
-        transform: Transform {
-            translation: Vec3 {
-                x: -225.,
-                y: 225.,
-                z: 1.,
+        _ => None,
+    };
+    let replay = match data_path("game.replay").map(File::open) {
+        Some(Ok(file)) if saved.is_some() => match Replay::read(BufReader::new(file)) {
+            Ok(replay) => Some(replay),
+            Err(e) => {
+                toasts.send(Toast::error(format!("Could not load the saved replay: {}", e)));
+                None
+            }
+        },
+        _ => None,
+    };
+    c.insert_resource(SavedGame { value: saved, replay });
+
+    let scores = match data_path("leaderboard.json").map(File::open) {
+        Some(Ok(file)) => Leaderboard::load(BufReader::new(file)).unwrap_or_else(|e| {
+            toasts.send(Toast::error(format!("Could not load the leaderboard: {}", e)));
+            Leaderboard::default()
+        }),
+        _ => Leaderboard::default(),
+    };
+    c.insert_resource(Scores { value: scores });
+
+    c.spawn(NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(10.),
+                ..default()
             },
+            size: Size::width(Val::Percent(100.)),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
             ..default()
         },
-        text_anchor: Anchor::TopLeft,
+        z_index: ZIndex::Global(10),
         ..default()
-    });
+    })
+    .insert(ToastList);
+}
 
-    // spawn input text 
-    c.spawn(Text2dBundle {
-        text: Text {
-            sections: vec![TextSection {
-                value: "10".to_owned(),
-                style: TextStyle {
+// shows new toasts and fades out the old ones
+pub fn show_toasts(
+    time: Res<Time>,
+    gr: Res<GameRes>,
+    mut c: Commands,
+    mut toasts: EventReader<Toast>,
+    list_query: Query<Entity, With<ToastList>>,
+    mut toast_query: Query<(Entity, &mut ToastText, &mut Text, &mut BackgroundColor)>,
+) {
+    for (e, mut toast, mut text, mut background) in &mut toast_query {
+        toast.timer.tick(time.delta());
+        if toast.timer.finished() {
+            c.entity(e).despawn();
+            continue;
+        }
+        let alpha = f32::min(toast.timer.remaining_secs() / TOAST_FADE, 1.);
+        text.sections[0].style.color.set_a(alpha);
+        background.0.set_a(alpha * 0.8);
+    }
+
+    let Ok(list) = list_query.get_single() else {
+        return;
+    };
+    for toast in toasts.iter() {
+        let color = match toast.error {
+            true => Color::rgb(1.0, 0.3, 0.3),
+            false => Color::rgb(0.9, 0.9, 0.9),
+        };
+        let text = c.spawn(TextBundle {
+            text: Text::from_section(
+                toast.message.clone(),
+                TextStyle {
                     font: gr.font.clone(),
-                    font_size: INPUT_TEXT_FONT_SIZE,
-                    color: Color::rgb(0.9, 0.9, 0.9),
+                    font_size: INTRO_FONT_SIZE / 2.,
+                    color,
                 },
-            }],
-            ..default()
-        },
-        transform: Transform {
-            translation: Vec3 {
-                x: -225.,
-                y: 175.,
-                z: 1.,
+            ),
+            style: Style {
+                margin: UiRect::all(Val::Px(5.)),
+                padding: UiRect::all(Val::Px(10.)),
+                ..default()
             },
+            background_color: Color::rgba(0., 0., 0., 0.8).into(),
             ..default()
-        },
-        text_anchor: Anchor::TopLeft,
-        ..default()
-    })
-    .insert(InputText);
+        })
+        .insert(ToastText {
+            timer: Timer::from_seconds(TOAST_TIME, TimerMode::Once),
+        })
+        .id();
+        c.entity(list).add_child(text);
+    }
+}
+
+pub fn save_on_exit(
+    mut exit: EventReader<AppExit>,
+    state: Res<State<GameState>>,
+    game: Res<Game>,
+    saved: Res<SavedGame>,
+) {
+    // a saved game that was not continued stays in its file
+    if exit.iter().next().is_none() || saved.value.is_some() {
+        return;
+    }
+    let (Some(path), Some(replay_path)) = (data_path("game.sav"), data_path("game.replay")) else {
+        return;
+    };
+
+    // only a game in progress is worth continuing
+    if state.0 != GameState::Playing || !game.ms.playing {
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(replay_path);
+        return;
+    }
+
+    // the window is closing, errors can only go to stderr
+    match create_data_file(&path) {
+        Ok(file) => {
+            if let Err(e) = game.ms.save(file, Format::Binary) {
+                eprintln!("Could not save the game: {}", e);
+            }
+        }
+        Err(e) => eprintln!("Could not save the game: {}", e),
+    }
+    if let Err(e) = create_data_file(&replay_path).and_then(|file| game.replay.write(file)) {
+        eprintln!("Could not save the replay: {}", e);
+    }
+}
+
+pub fn init(
+    gr: Res<GameRes>,
+    ms_info: Res<MSInfo>,
+    saved: Res<SavedGame>,
+    mut c: Commands,
+) {
+    c.insert_resource(Fields::new(&ms_info));
 
-    // spawn start button
-    c.spawn(ButtonBundle {
+    let button = ButtonBundle {
         style: Style {
             size: Size::new(Val::Px(450.), Val::Px(100.)),
-            margin: UiRect::all(Val::Auto),
+            margin: UiRect::all(Val::Px(10.)),
             justify_content: JustifyContent::Center,
             align_items: AlignItems::Center,
             ..default()
         },
         background_color: BackgroundColor::from(NORMAL_BUTTON),
-        transform: Transform {
-            translation: Vec3 {
-                x: 0.,
-                y: 0.,
-                z: 1.
-            },
+        ..default()
+    };
+
+    c.spawn(NodeBundle {
+        style: Style {
+            margin: UiRect::all(Val::Auto),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
             ..default()
         },
         ..default()
     })
+    .insert(Menu)
     .with_children(|parent| {
         parent.spawn(TextBundle::from_section(
-            "Start!",
+            "Difficulty:",
             TextStyle {
                 font: gr.font.clone(),
-                font_size: 80.,
-                color: Color::rgb(1., 1., 1.)
+                font_size: INTRO_FONT_SIZE,
+                color: Color::rgb(0.9, 0.9, 0.9),
             }
         ));
+
+        // spawn presets in a row
+        parent.spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Menu)
+        .with_children(|parent| {
+            for preset in Preset::ALL {
+                parent.spawn(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(210.), Val::Px(70.)),
+                        margin: UiRect::all(Val::Px(10.)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: BackgroundColor::from(NORMAL_BUTTON),
+                    ..default()
+                })
+                .insert(preset)
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        preset.label(),
+                        TextStyle {
+                            font: gr.font.clone(),
+                            font_size: INPUT_TEXT_FONT_SIZE / 4.,
+                            color: Color::rgb(1., 1., 1.)
+                        }
+                    ));
+                });
+            }
+        });
+
+        // spawn width, height and mines fields in a row, shown for custom games
+        parent.spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Row,
+                display: match ms_info.difficulty {
+                    Difficulty::Custom { .. } => Display::Flex,
+                    _ => Display::None,
+                },
+                ..default()
+            },
+            ..default()
+        })
+        .insert((Menu, FieldRow))
+        .with_children(|parent| {
+            for field in Field::ALL {
+                parent.spawn(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(240.), Val::Px(80.)),
+                        margin: UiRect::all(Val::Px(10.)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: BackgroundColor::from(NORMAL_BUTTON),
+                    ..default()
+                })
+                .insert(field)
+                .with_children(|parent| {
+                    let style = TextStyle {
+                        font: gr.font.clone(),
+                        font_size: INPUT_TEXT_FONT_SIZE / 3.,
+                        color: Color::rgb(1., 1., 1.)
+                    };
+                    // label, value and cursor
+                    parent.spawn(TextBundle::from_sections([
+                        TextSection::new(field.label(), style.clone()),
+                        TextSection::new("", style.clone()),
+                        TextSection::new("|", style),
+                    ]))
+                    .insert(field);
+                });
+            }
+        });
+
+        parent.spawn(TextBundle::from_section(
+            "",
+            TextStyle {
+                font: gr.font.clone(),
+                font_size: INTRO_FONT_SIZE / 2.,
+                color: Color::rgb(1.0, 0.3, 0.3),
+            }
+        ))
+        .insert(FieldError);
+
+        if saved.value.is_some() {
+            parent.spawn(button.clone())
+                .insert(ContinueButton)
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Continue",
+                        TextStyle {
+                            font: gr.font.clone(),
+                            font_size: 80.,
+                            color: Color::rgb(1., 1., 1.)
+                        }
+                    ));
+                });
+        }
+
+        // spawn start button
+        parent.spawn(button.clone())
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "Start!",
+                    TextStyle {
+                        font: gr.font.clone(),
+                        font_size: 80.,
+                        color: Color::rgb(1., 1., 1.)
+                    }
+                ));
+            });
+
+        parent.spawn(button.clone())
+            .insert(LeaderboardButton)
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    "Leaderboard",
+                    TextStyle {
+                        font: gr.font.clone(),
+                        font_size: 50.,
+                        color: Color::rgb(1., 1., 1.)
+                    }
+                ));
+            });
+
+        // spawn option toggles
+        for toggle in [Toggle::NoGuess, Toggle::Practice] {
+            parent.spawn(button.clone())
+                .insert(toggle)
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        toggle.label(&ms_info),
+                        TextStyle {
+                            font: gr.font.clone(),
+                            font_size: 50.,
+                            color: Color::rgb(1., 1., 1.)
+                        }
+                    ))
+                    .insert(toggle);
+                });
+        }
     });
 }
 
+// a preset replaces the difficulty right away, custom opens the fields
+pub fn choose_preset(
+    mut ms_info: ResMut<MSInfo>,
+    mut fields: ResMut<Fields>,
+    mut interaction_query: Query<(&Interaction, &Preset, &mut BackgroundColor), With<Button>>,
+    mut row_query: Query<&mut Style, With<FieldRow>>,
+) {
+    for (interaction, preset, _) in &interaction_query {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        fields.custom = preset.0.is_none();
+        if let Some(difficulty) = preset.0 {
+            ms_info.difficulty = difficulty;
+            // custom games start from the last preset
+            let (width, height, mines) = difficulty.size();
+            fields.values = [width, height, mines].map(|v| v.to_string());
+        }
+    }
+
+    let chosen = match fields.custom {
+        true => Preset(None),
+        false => Preset(Some(ms_info.difficulty)),
+    };
+    for (interaction, preset, mut color) in &mut interaction_query {
+        *color = match (*preset == chosen, *interaction) {
+            (true, _) => PRESSED_BUTTON.into(),
+            (false, Interaction::Hovered) => HOVERED_BUTTON.into(),
+            (false, _) => NORMAL_BUTTON.into(),
+        };
+    }
+    for mut style in &mut row_query {
+        style.display = match fields.custom {
+            true => Display::Flex,
+            false => Display::None,
+        };
+    }
+}
+
+// typing, Tab and clicks edit the intro fields, which are checked on every change
+pub fn edit_fields(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    ms_info: Res<MSInfo>,
+    mut fields: ResMut<Fields>,
+    mut char_evr: EventReader<ReceivedCharacter>,
+    mut interaction_query: Query<(&Interaction, &Field, &mut BackgroundColor), With<Button>>,
+    mut text_query: Query<(&mut Text, &Field)>,
+    mut error_query: Query<&mut Text, (With<FieldError>, Without<Field>)>,
+) {
+    if !fields.custom {
+        for mut text in &mut error_query {
+            text.sections[0].value.clear();
+        }
+        return;
+    }
+
+    for (interaction, field, _) in &interaction_query {
+        if *interaction == Interaction::Clicked {
+            fields.focus = *field;
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Tab) {
+        let shift = keys.pressed(KeyCode::LShift) || keys.pressed(KeyCode::RShift);
+        let step = if shift { Field::ALL.len() - 1 } else { 1 };
+        fields.focus = Field::ALL[(fields.focus as usize + step) % Field::ALL.len()];
+    }
+
+    let focus = fields.focus as usize;
+    let value = &mut fields.values[focus];
+    for ev in char_evr.iter() {
+        if ev.char.is_ascii_digit() && value.len() < Field::MAX_DIGITS {
+            value.push(ev.char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        value.pop();
+    }
+
+    let error = fields.info(&ms_info).err();
+    // the cursor of the focused field blinks twice a second
+    let blink = (time.elapsed_seconds() * 2.) as u32 % 2 == 0;
+
+    for (mut text, field) in &mut text_query {
+        text.sections[1].value = fields.values[*field as usize].clone();
+        text.sections[1].style.color = match error {
+            Some((wrong, _)) if wrong == *field => Color::rgb(1.0, 0.3, 0.3),
+            _ => Color::rgb(1., 1., 1.),
+        };
+        text.sections[2].style.color = match *field == fields.focus && blink {
+            true => Color::rgb(1., 1., 1.),
+            false => Color::NONE,
+        };
+    }
+    for (_, field, mut color) in &mut interaction_query {
+        *color = match *field == fields.focus {
+            true => PRESSED_BUTTON.into(),
+            false => NORMAL_BUTTON.into(),
+        };
+    }
+    for mut text in &mut error_query {
+        text.sections[0].value = error.as_ref().map_or(String::new(), |(_, e)| e.clone());
+    }
+}
+
+// shows or hides the best time of every board on the intro screen
+pub fn toggle_leaderboard(
+    gr: Res<GameRes>,
+    scores: Res<Scores>,
+    mut c: Commands,
+    mut clicked: Local<bool>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor),
+        (Changed<Interaction>, With<LeaderboardButton>),
+    >,
+    text_query: Query<Entity, With<LeaderboardText>>,
+) {
+    let mut pressed = false;
+    for (interaction, mut color) in &mut interaction_query {
+        match *interaction {
+            Interaction::Clicked => {
+                *color = PRESSED_BUTTON.into();
+                *clicked = true;
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+                pressed = *clicked;
+                *clicked = false;
+            }
+            Interaction::None => {
+                *color = NORMAL_BUTTON.into();
+            }
+        }
+    }
+    if !pressed {
+        return;
+    }
+
+    if !text_query.is_empty() {
+        for e in text_query.iter() {
+            c.entity(e).despawn();
+        }
+        return;
+    }
+
+    let mut table = "Best times:".to_owned();
+    for entry in scores.value.best() {
+        table += &format!(
+            "\n{}: {:.2}s by {} on {}",
+            entry.difficulty,
+            entry.time.as_secs_f32(),
+            entry.name,
+            entry.date_string(),
+        );
+    }
+    if scores.value.best().is_empty() {
+        table += "\nnone yet, win a game!";
+    }
+
+    c.spawn(TextBundle::from_section(
+        table,
+        TextStyle {
+            font: gr.font.clone(),
+            font_size: INTRO_FONT_SIZE / 3.,
+            color: Color::rgb(0.9, 0.9, 0.9),
+        },
+    ).with_style(Style {
+        position_type: PositionType::Absolute,
+        position: UiRect {
+            left: Val::Px(10.),
+            bottom: Val::Px(10.),
+            ..default()
+        },
+        ..default()
+    }))
+    .insert(LeaderboardText);
+}
+
 pub fn init_ms(
-    a: Res<AssetServer>,
+    gr: Res<GameRes>,
     keys: Res<Input<KeyCode>>,
     mut c: Commands, 
-    mut char_evr: EventReader<ReceivedCharacter>,
     mut ms_info: ResMut<MSInfo>,
+    fields: Res<Fields>,
+    mut saved: ResMut<SavedGame>,
     mut state: ResMut<NextState<GameState>>,
+    mut toasts: EventWriter<Toast>,
     mut chosen: Local<bool>,
     mut clicked: Local<bool>,
     mut pressed: Local<bool>,
-    mut text_query: Query<&mut Text, With<InputText>>,
+    mut toggle: Local<Option<Toggle>>,
+    mut resume: Local<bool>,
+    mut toggle_text_query: Query<(&mut Text, &Toggle)>,
     mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor),
-        (Changed<Interaction>, With<Button>),
+        (&Interaction, &mut BackgroundColor, Option<&Toggle>, Option<&ContinueButton>),
+        (Changed<Interaction>, With<Button>, Without<LeaderboardButton>, Without<Field>, Without<Preset>),
     >,
-    button_entity_query: Query<Entity, With<Button>>,
-    button_text_entity_query: Query<Entity, With<Text>>,
+    menu_entity_query: Query<Entity, (Or<(With<Button>, With<Text>, With<Menu>)>, Without<ToastText>)>,
 ) {
     if !*chosen {
-        for mut text in &mut text_query {
-            let input_text = &mut text.sections[0].value;
-
-            for ev in char_evr.iter() {
-                if '0' <= ev.char && ev.char <= '9' { 
-                    input_text.push(ev.char);
+        for (interaction, mut color, toggle_button, continue_button) in &mut interaction_query {
+            match *interaction {
+                Interaction::Clicked => {
+                    *color = PRESSED_BUTTON.into();
+                    *clicked = true;
+                    *toggle = toggle_button.copied();
+                    *resume = continue_button.is_some();
                 }
-            }
-
-            for (interaction, mut color) in &mut interaction_query {
-                match *interaction {
-                    Interaction::Clicked => {
-                        *color = PRESSED_BUTTON.into();
-                        *clicked = true;
-                    }
-                    Interaction::Hovered => {
-                        *color = HOVERED_BUTTON.into();
-                        if *clicked {
-                            *pressed = true;
-                            *clicked = false;
-                        }
-                    }
-                    Interaction::None => {
-                        *color = NORMAL_BUTTON.into();
+                Interaction::Hovered => {
+                    *color = HOVERED_BUTTON.into();
+                    if *clicked {
+                        *pressed = true;
+                        *clicked = false;
                     }
                 }
+                Interaction::None => {
+                    *color = NORMAL_BUTTON.into();
+                }
             }
+        }
 
-            if keys.just_pressed(KeyCode::Back) {
-                if !input_text.is_empty() {
-                    input_text.pop();
-                }
-            } else if keys.just_pressed(KeyCode::Return) || *pressed {
-                *pressed = false;
-                // handle typed number
-                match (*input_text).trim().parse::<isize>() {
-                    Ok(ms_size) => {
-                        if ms_size > 1 {
-                            input_text.clear();
-                            // change info of ms_info
-                            *ms_info = MSInfo {
-                                width: ms_size as usize,
-                                height: ms_size as usize,
-                                mines: max(1, ms_size * ms_size / 10) as usize,
-                            };
-                            *chosen = true;
-                        } else {
-                            println!("Invalid number!"); // TODO make err graphical
-                        }
-                    },
-                    _ => {
-                        println!("Wrong input!"); // TODO make err graphical
-                    }
+        if let (true, Some(toggle)) = (*pressed, *toggle) {
+            *pressed = false;
+            toggle.flip(&mut ms_info);
+            for (mut text, toggle) in &mut toggle_text_query {
+                text.sections[0].value = toggle.label(&ms_info);
+            }
+        } else if *pressed && *resume {
+            *pressed = false;
+            if let Some(ms) = saved.value.take() {
+                *ms_info = MSInfo::from_game(&ms);
+                c.insert_resource(Game {
+                    resumed: true,
+                    // without its replay the game is recorded from where it was left
+                    replay: saved.replay.take().unwrap_or_else(|| Replay::new(&ms)),
+                    ms,
+                    start: None,
+                    elapsed: Duration::ZERO,
+                    generating: None,
+                });
+                *chosen = true;
+            }
+        } else if keys.just_pressed(KeyCode::Return) || *pressed {
+            *pressed = false;
+            let info = match fields.custom {
+                true => fields.info(&ms_info),
+                false => Ok(MSInfo { seed: None, ..*ms_info }),
+            };
+            match info {
+                Ok(info) => {
+                    *ms_info = info;
+                    // a new game replaces the saved one
+                    saved.value = None;
+                    saved.replay = None;
+                    *chosen = true;
                 }
+                // the wrong field is already marked by `edit_fields`
+                Err((field, e)) => toasts.send(Toast::error(format!("{}{}", field.label(), e))),
             }
         }
     }
@@ -247,51 +1086,55 @@ pub fn init_ms(
         *chosen = false;
         *pressed = false;
 
-        for e in button_text_entity_query.iter() {
-            c.entity(e).despawn();
-        }
-        for e in button_entity_query.iter() {
+        for e in menu_entity_query.iter() {
             c.entity(e).despawn();
         }
 
         c.insert_resource(*ms_info);
-
-        for _ in 0..ms_info.width * ms_info.height {
-            c.spawn(SpriteBundle {
-                texture: a.load(Path::new("img").join("cell.png")),
-                sprite: Sprite {
-                    color: Color::Rgba{red: 1., green: 1., blue: 1., alpha: 1.},
-                    custom_size: Some(Vec2::new(1., 1.)),
-                    ..default()
-                },
-                ..default()
-            })
-            .insert(MS);
-        }
+        let (width, height, _) = ms_info.difficulty.size();
+        spawn_cells(&mut c, &gr, width, height);
 
         state.set(GameState::Playing);
     }
 }
 
 pub fn run_ms(
-    // time: Res<Time>,
+    time: Res<Time>,
     gr: Res<GameRes>,
-    ms_info: Res<MSInfo>,
+    mut ms_info: ResMut<MSInfo>,
+    keys: Res<Input<KeyCode>>,
     mouse_button_input: Res<Input<MouseButton>>,
     window_query: Query<&Window, With<PrimaryWindow>>,
     mut state: ResMut<NextState<GameState>>,
     mut game_won: ResMut<GameWon>,
     mut cursor_moved: EventReader<CursorMoved>,
-    mut ms: Local<Minesweeper>,
+    mut game: ResMut<Game>,
     mut second_frame: Local<bool>,
+    mut chording: Local<bool>,
+    mut overlay: Local<Overlay>,
     mut cursor_position: Local<Vec2>,
     mut sprites: Query<(&mut Sprite, &mut Transform, &mut Handle<Image>), With<MS>>,
 ) {
+    let Game { ms, resumed, replay, start, elapsed, generating } = &mut *game;
+
     if !*second_frame {
-        *ms = Minesweeper::new(ms_info.width, ms_info.height, ms_info.mines);
+        *generating = None;
+        if !std::mem::take(resumed) {
+            *ms = ms_info.builder().build().expect("grid size is validated on the intro screen");
+            *replay = Replay::new(ms);
+        }
+        // a continued game goes on from its last input
+        *elapsed = replay.records.last().map_or(Duration::ZERO, |r| r.time);
+        *start = match ms.number_of_revealed_cells() {
+            0 => None,
+            _ => Some(time.elapsed().saturating_sub(*elapsed)),
+        };
+        ms_info.seed = Some(ms.seed);
+        *overlay = Overlay::default();
         *second_frame = true;
-        for (mut s, mut _p, mut _i) in &mut sprites{
+        for (mut s, mut _p, mut i) in &mut sprites{
             s.color = Color::rgb(1., 1., 1.);
+            *i = gr.imgs.get("cell").unwrap().clone();
         }
         return;
     }
@@ -317,16 +1160,59 @@ pub fn run_ms(
         *cursor_position = moved_cursor.position;    
     }
 
-    let left_click = mouse_button_input.just_released(MouseButton::Left);
-    let right_click = mouse_button_input.just_released(MouseButton::Right);
+    let mut left_click = mouse_button_input.just_released(MouseButton::Left);
+    let mut right_click = mouse_button_input.just_released(MouseButton::Right);
+    let mut chord_click = mouse_button_input.just_released(MouseButton::Middle);
+
+    // left+right chord fires when the first of the two buttons is released,
+    // releasing the second one afterwards does nothing
+    if mouse_button_input.pressed(MouseButton::Left) && mouse_button_input.pressed(MouseButton::Right) {
+        *chording = true;
+    }
+    if *chording && (left_click || right_click) {
+        let still_held = mouse_button_input.pressed(MouseButton::Left)
+            || mouse_button_input.pressed(MouseButton::Right);
+        chord_click |= still_held || (left_click && right_click);
+        if !still_held {
+            *chording = false;
+        }
+        left_click = false;
+        right_click = false;
+    }
+
+    let mut input = None;
+
+    let ctrl = keys.pressed(KeyCode::LControl) || keys.pressed(KeyCode::RControl);
+    if ctrl && keys.just_pressed(KeyCode::Z) {
+        input = Some(replay::Input::Undo);
+    } else if ctrl && keys.just_pressed(KeyCode::Y) {
+        input = Some(replay::Input::Redo);
+    }
+
+    // toggle mine probability overlay
+    if keys.just_pressed(KeyCode::P) {
+        overlay.heatmap = match overlay.heatmap.is_some() {
+            true => None,
+            false => Some(mine_probabilities(ms)),
+        };
+    }
+
+    // the hint would be lost when the generated game replaces this one
+    if keys.just_pressed(KeyCode::H) && generating.is_none() {
+        overlay.hinted = hint(ms);
+        if overlay.hinted.is_some() {
+            let now = start.map_or(Duration::ZERO, |start| time.elapsed().saturating_sub(start));
+            replay.record(ms, replay::Input::Hint, now).expect("a hint does not change the game");
+        }
+    }
 
     let mx = cursor_position.x;
     let my = cursor_position.y;
 
     // main game loop
-    for (ind, (mut s, mut t, mut i)) in (&mut sprites).into_iter().enumerate() {
-        let x = ind % ms.width;
-        let y = ind / ms.width;
+    for (ind, (mut s, mut t, _i)) in (&mut sprites).into_iter().enumerate() {
+        let coord = ms.grid().coord(ind);
+        let Coord { x, y } = coord;
 
         let tx = pad_x + (x as f32 - ms.width as f32  / 2.) * size;
         let ty = pad_y + (y as f32 - ms.height as f32 / 2.) * size;
@@ -341,6 +1227,15 @@ pub fn run_ms(
         };
         *t = trans;
 
+        // cells more likely to hold a mine are tinted red, the hinted one green
+        let color = if overlay.hinted == Some(coord) {
+            Color::rgb(0.4, 1., 0.4)
+        } else if let Some(p) = overlay.heatmap.as_ref().and_then(|h| h[coord]) {
+            Color::rgb(1., 1. - p as f32, 1. - p as f32)
+        } else {
+            Color::rgb(1., 1., 1.)
+        };
+
         let collision_trans = Transform {
             translation: Vec3::new(
                 tx + window.width()/2.,
@@ -357,48 +1252,85 @@ pub fn run_ms(
             Vec2::new(1.0, 1.0)
         ) {
             if collision == Collision::Inside {
-                if left_click {
-                    ms.open(x, y);
+                if chord_click {
+                    input = Some(replay::Input::Action(Action::Chord(x, y)));
+                } else if left_click {
+                    input = Some(replay::Input::Action(Action::Open(x, y)));
                 } else if right_click {
-                    ms.flag(x, y);
-                    if ms.grid[y][x].flag {
-                        *i = gr.imgs.get("flag").unwrap().clone();
-                    } else {
-                        *i = gr.imgs.get("cell").unwrap().clone();
-                    }
+                    input = Some(replay::Input::Action(Action::Flag(x, y)));
                 } else {
-                    s.color = Color::rgb(0.8, 0.8, 0.8);
+                    s.color = Color::rgb(color.r() * 0.8, color.g() * 0.8, color.b() * 0.8);
                 }
             }
         } else {
-            s.color = Color::rgb(1.0, 1.0, 1.0);
+            s.color = color;
         }
 
         s.custom_size = size_vec;
+    }
 
-        // change sprite
-        if ms.grid[y][x].revealed {
-            if ms.grid[y][x].mine {
-                *i = gr.imgs.get("mine").unwrap().clone();
-            } else {
-                let surr = ms.grid[y][x].surrounds;
-                *i = gr.imgs.get(&surr.to_string()).unwrap().clone();
-            }
+    let mut changed = false;
+    if let Some(task) = generating {
+        // inputs wait for the grid
+        if let Some((generated, recorded, applied)) = future::block_on(future::poll_once(task)) {
+            *generating = None;
+            *ms = generated;
+            *replay = recorded;
+            changed = applied;
         }
+    } else if let Some(input) = input {
+        // inputs before the clock starts are at 0, like the one starting it
+        let now = start.map_or(Duration::ZERO, |start| time.elapsed().saturating_sub(start));
+        let generates = !ms.mines_placed()
+            && ms.generation() != GenerationPolicy::Random
+            && matches!(input, replay::Input::Action(Action::Open(..) | Action::Chord(..)) | replay::Input::Redo);
+        if generates {
+            // finding a grid without guesses can take a while
+            let (mut ms, mut replay) = (ms.clone(), replay.clone());
+            *generating = Some(AsyncComputeTaskPool::get().spawn(async move {
+                let applied = replay.record(&mut ms, input, now).is_ok();
+                (ms, replay, applied)
+            }));
+        } else {
+            changed = replay.record(ms, input, now).is_ok();
+        }
+    }
+    // the clock starts with the first revealed cell
+    if changed && start.is_none() && ms.number_of_revealed_cells() > 0 {
+        *start = Some(time.elapsed());
+    }
+    if let (true, Some(start)) = (ms.playing, *start) {
+        *elapsed = time.elapsed().saturating_sub(start);
+    }
+
+    // change sprites, undo can hide revealed cells again
+    for (cell, (mut _s, mut _p, mut i)) in ms.grid().iter().zip(&mut sprites) {
+        *i = gr.imgs.get(&cell_image(*cell)).unwrap().clone();
+    }
+
+    // in practice mode a lost game waits for the fatal click to be undone,
+    // Enter gives up
+    let practice_loss = !ms.playing && !ms.won && ms.practice();
+    if !ms.playing && (!practice_loss || keys.just_pressed(KeyCode::Return)) {
+        *second_frame = false;
+        game_won.value = ms.won;
+    }
 
-        if !ms.playing {
-            *second_frame = false;
-            game_won.value = ms.won;
+    if changed {
+        if overlay.heatmap.is_some() {
+            overlay.heatmap = Some(mine_probabilities(ms));
+        }
+        if let Some(c) = overlay.hinted {
+            if ms.grid()[c].revealed || ms.grid()[c].flag {
+                overlay.hinted = None;
+            }
         }
     }
 
     if !*second_frame {
         if !game_won.value {
-            for (ind, (mut _s, mut _p, mut i)) in (&mut sprites).into_iter().enumerate() {
-                let x = ind % ms.width;
-                let y = ind / ms.width;
-
-                if ms.grid[y][x].mine {
+            for (cell, (mut _s, mut _p, mut i)) in ms.grid().iter().zip(&mut sprites) {
+                if cell.mine {
                     *i = gr.imgs.get("mine").unwrap().clone();
                 }
             }
@@ -407,11 +1339,97 @@ pub fn run_ms(
     }
 }
 
+pub fn hud_init(
+    gr: Res<GameRes>,
+    mut c: Commands,
+) {
+    c.spawn(Text2dBundle {
+        text: Text::from_section(
+            "",
+            TextStyle {
+                font: gr.font.clone(),
+                font_size: INTRO_FONT_SIZE / 2.,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+        ),
+        text_anchor: Anchor::TopLeft,
+        ..default()
+    })
+    .insert(Hud);
+}
+
+pub fn update_hud(
+    game: Res<Game>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut hud_query: Query<(&mut Text, &mut Transform), With<Hud>>,
+) {
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+
+    // more flags than mines makes the counter negative
+    let mines_left = game.ms.number_of_mines() as isize - game.ms.number_of_flags() as isize;
+    for (mut text, mut t) in &mut hud_query {
+        text.sections[0].value = format!("Time: {}   Mines: {}", game.elapsed.as_secs(), mines_left);
+        t.translation = Vec3::new(-window.width() / 2. + 10., window.height() / 2. - 10., 2.);
+    }
+}
+
+pub fn hud_exit(
+    mut c: Commands,
+    hud_query: Query<Entity, With<Hud>>,
+) {
+    for e in hud_query.iter() {
+        c.entity(e).despawn();
+    }
+}
+
+// the best times of a difficulty, marking the given place
+fn record_table(scores: &Leaderboard, difficulty: Difficulty, place: Option<usize>) -> String {
+    let mut table = format!("\n\nTop {} for {}:", TOP, difficulty);
+    for (i, entry) in scores.top(difficulty).iter().enumerate() {
+        table += &format!(
+            "\n{}{}. {:.2}s  {:.2} 3BV/s  {}  {}",
+            if place == Some(i) { "> " } else { "" },
+            i + 1,
+            entry.time.as_secs_f32(),
+            entry.bbbv_per_second,
+            entry.name,
+            entry.date_string(),
+        );
+    }
+    table
+}
+
+fn save_scores(scores: &Leaderboard, toasts: &mut EventWriter<Toast>) {
+    let Some(path) = data_path("leaderboard.json") else {
+        return;
+    };
+    let result = create_data_file(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| scores.save(file).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        toasts.send(Toast::error(format!("Could not save the leaderboard: {}", e)));
+    }
+}
+
 pub fn endgame_init(
     gr: Res<GameRes>,
+    ms_info: Res<MSInfo>,
+    game: Res<Game>,
+    name: Res<PlayerName>,
+    mut scores: ResMut<Scores>,
     mut c: Commands,
+    mut toasts: EventWriter<Toast>,
     game_won: ResMut<GameWon>,
 ) {
+    // keep the last game to share it
+    if let Some(path) = data_path("last.replay") {
+        if let Err(e) = create_data_file(&path).and_then(|file| game.replay.write(file)) {
+            toasts.send(Toast::error(format!("Could not save the replay: {}", e)));
+        }
+    }
+
     let mut win_text = "Game Over!";
     let mut text_color = Color::rgb(1.0, 0.1, 0.1);
     if game_won.value {
@@ -419,16 +1437,99 @@ pub fn endgame_init(
         text_color = Color::rgb(0.1, 1.0, 0.1);
     }
 
-    c.spawn(Text2dBundle {
+    let mut sections = vec![TextSection {
+        value: win_text.to_owned(),
+        style: TextStyle {
+            font: gr.font.clone(),
+            font_size: INTRO_FONT_SIZE,
+            color: text_color,
+        },
+    }];
+    if let Some(seed) = ms_info.seed {
+        sections.push(TextSection {
+            value: format!("\nSeed: {}", seed),
+            style: TextStyle {
+                font: gr.font.clone(),
+                font_size: INTRO_FONT_SIZE / 2.,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+        });
+    }
+    if game.hints() > 0 {
+        sections.push(TextSection {
+            value: format!("\nHints used: {}", game.hints()),
+            style: TextStyle {
+                font: gr.font.clone(),
+                font_size: INTRO_FONT_SIZE / 2.,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+        });
+    }
+
+    sections.push(TextSection {
+        value: format!("\n{}  Time: {:.1}s", game.ms.difficulty(), game.elapsed.as_secs_f32()),
+        style: TextStyle {
+            font: gr.font.clone(),
+            font_size: INTRO_FONT_SIZE / 2.,
+            color: Color::rgb(0.9, 0.9, 0.9),
+        },
+    });
+
+    let clicks = game.replay.records.iter().filter(|r| matches!(r.input, replay::Input::Action(_))).count();
+    let stats = game_stats(&game.ms, clicks, game.elapsed);
+    sections.push(TextSection {
+        value: format!(
+            "\n3BV: {}/{}  Openings: {}  Islands: {}\nClicks: {}  Efficiency (IOE): {:.2}  3BV/s: {:.2}",
+            stats.solved_bbbv,
+            stats.board.bbbv,
+            stats.board.openings,
+            stats.board.islands,
+            stats.clicks,
+            stats.efficiency(),
+            stats.bbbv_per_second(),
+        ),
+        style: TextStyle {
+            font: gr.font.clone(),
+            font_size: INTRO_FONT_SIZE / 2.,
+            color: Color::rgb(0.9, 0.9, 0.9),
+        },
+    });
+
+    // help from hints or undoing a lost game does not make a record
+    let mut record = None;
+    if game_won.value {
+        let difficulty = game.ms.difficulty();
+        let place = match game.ms.practice() || game.hints() > 0 {
+            true => None,
+            false => {
+                let entry = Entry::new(difficulty, name.value.clone(), game.elapsed, stats.bbbv_per_second());
+                scores.value.insert(entry)
+            }
+        };
+        if place.is_some() {
+            save_scores(&scores.value, &mut toasts);
+        }
+        if place == Some(0) {
+            toasts.send(Toast::info(format!("New best time for {}!", difficulty)));
+        }
+
+        let style = TextStyle {
+            font: gr.font.clone(),
+            font_size: INTRO_FONT_SIZE / 3.,
+            color: Color::rgb(0.9, 0.9, 0.9),
+        };
+        sections.push(TextSection::new(record_table(&scores.value, difficulty, place), style.clone()));
+        if let Some(place) = place {
+            // name and cursor, see `edit_record_name`
+            record = Some(RecordName { place, table: sections.len() - 1 });
+            sections.push(TextSection::new(format!("\nName: {}", name.value), style.clone()));
+            sections.push(TextSection::new("|", style));
+        }
+    }
+
+    let mut text = c.spawn(Text2dBundle {
         text: Text {
-            sections: vec![TextSection {
-                value: win_text.to_owned(),
-                style: TextStyle {
-                    font: gr.font.clone(),
-                    font_size: INTRO_FONT_SIZE,
-                    color: text_color,
-                },
-            }],
+            sections,
             alignment: TextAlignment::Center,
             ..default()
         },
@@ -442,48 +1543,113 @@ pub fn endgame_init(
         },
         ..default()
     });
-    
-    c.spawn(ButtonBundle {
+    if let Some(record) = record {
+        text.insert(record);
+    }
+
+    let button = ButtonBundle {
         style: Style {
             size: Size::new(Val::Px(450.), Val::Px(100.)),
-            margin: UiRect::all(Val::Auto),
+            margin: UiRect::all(Val::Px(10.)),
             justify_content: JustifyContent::Center,
             align_items: AlignItems::Center,
             ..default()
         },
         background_color: BackgroundColor::from(Color::rgb(0.6, 0.6, 0.6)),
-        transform: Transform {
-            translation: Vec3 {
-                x: 0.,
-                y: 0.,
-                z: 1.
-            },
+        ..default()
+    };
+    let button_text_style = TextStyle {
+        font: gr.font.clone(),
+        font_size: 80.,
+        color: Color::rgb(1., 1., 1.)
+    };
+
+    c.spawn(NodeBundle {
+        style: Style {
+            margin: UiRect::all(Val::Auto),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
             ..default()
         },
         ..default()
     })
+    .insert(Menu)
     .with_children(|parent| {
-        parent.spawn(TextBundle::from_section(
-            "Restart",
-            TextStyle {
-                font: gr.font.clone(),
-                font_size: 80.,
-                color: Color::rgb(1., 1., 1.)
-            }
-        ));
+        parent.spawn(button.clone())
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section("Restart", button_text_style.clone()));
+            });
+
+        // start over on the very same board
+        parent.spawn(button.clone())
+            .insert(ReplayButton)
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section("Replay board", button_text_style.clone()));
+            });
+
+        parent.spawn(button)
+            .insert(WatchButton)
+            .with_children(|parent| {
+                parent.spawn(TextBundle::from_section("Watch replay", button_text_style));
+            });
     });
 }
 
+// typing renames a new record, it is saved on every change
+pub fn edit_record_name(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    game: Res<Game>,
+    mut name: ResMut<PlayerName>,
+    mut scores: ResMut<Scores>,
+    mut toasts: EventWriter<Toast>,
+    mut char_evr: EventReader<ReceivedCharacter>,
+    mut text_query: Query<(&mut Text, &RecordName)>,
+) {
+    let Ok((mut text, record)) = text_query.get_single_mut() else {
+        return;
+    };
+
+    let old = name.value.clone();
+    for ev in char_evr.iter() {
+        if !ev.char.is_control() && name.value.chars().count() < PlayerName::MAX_LEN {
+            name.value.push(ev.char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        name.value.pop();
+    }
+
+    if name.value != old {
+        let difficulty = game.ms.difficulty();
+        scores.value.rename(difficulty, record.place, name.value.clone());
+        save_scores(&scores.value, &mut toasts);
+        text.sections[record.table].value = record_table(&scores.value, difficulty, Some(record.place));
+        text.sections[record.table + 1].value = format!("\nName: {}", name.value);
+    }
+
+    // the cursor blinks twice a second like the one of the intro fields
+    let blink = (time.elapsed_seconds() * 2.) as u32 % 2 == 0;
+    text.sections[record.table + 2].style.color = match blink {
+        true => Color::rgb(0.9, 0.9, 0.9),
+        false => Color::NONE,
+    };
+}
+
 pub fn endgame(
     window_query: Query<&Window, With<PrimaryWindow>>,
-    ms_info: Res<MSInfo>,
+    game: Res<Game>,
+    mut ms_info: ResMut<MSInfo>,
     mut c: Commands,
     mut state: ResMut<NextState<GameState>>,
-    mut text_query: Query<(&Text, &mut Transform), Without<Button>>,
+    mut toasts: EventWriter<Toast>,
+    mut text_query: Query<(&Text, &mut Transform), (Without<Button>, Without<ToastText>)>,
     mut clicked: Local<bool>,
     mut pressed: Local<bool>,
+    mut replay: Local<bool>,
+    mut watch: Local<bool>,
     mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor),
+        (&Interaction, &mut BackgroundColor, Option<&ReplayButton>, Option<&WatchButton>),
         (Changed<Interaction>, With<Button>),
     >,
     mut ms_query: Query<
@@ -491,7 +1657,8 @@ pub fn endgame(
         (With<MS>, Without<Button>, Without<Text>),
     >,
     button_entity_query: Query<Entity, With<Button>>,
-    text_entity_query: Query<Entity, With<Text>>,
+    text_entity_query: Query<Entity, (With<Text>, Without<ToastText>)>,
+    menu_entity_query: Query<Entity, With<Menu>>,
 ) {
 
     let Ok(window) = window_query.get_single() else {
@@ -499,8 +1666,9 @@ pub fn endgame(
     };
 
     let (w, h) = (window.width(), window.height());
+    let (width, height, _) = ms_info.difficulty.size();
 
-    let grid_max = max(ms_info.width, ms_info.height) as f32;
+    let grid_max = max(width, height) as f32;
     // let grid_min = min(ms.width, ms.height) as f32;
     let wind_min = f32::min(w, h);
 
@@ -514,11 +1682,11 @@ pub fn endgame(
     let pad_y = size/2.;
 
     for (ind, (_e, mut s, mut t)) in (&mut ms_query).into_iter().enumerate() {
-        let x = ind % ms_info.width;
-        let y = ind / ms_info.width;
+        let x = ind % width;
+        let y = ind / width;
 
-        let tx = pad_x + (x as f32 - ms_info.width as f32  / 2.) * size;
-        let ty = pad_y + (y as f32 - ms_info.height as f32 / 2.) * size;
+        let tx = pad_x + (x as f32 - width as f32  / 2.) * size;
+        let ty = pad_y + (y as f32 - height as f32 / 2.) * size;
 
         let trans = Transform {
             translation: Vec3::new(
@@ -544,11 +1712,13 @@ pub fn endgame(
         }
     }
 
-    for (interaction, mut color) in &mut interaction_query {
+    for (interaction, mut color, replay_button, watch_button) in &mut interaction_query {
         match *interaction {
             Interaction::Clicked => {
                 *color = PRESSED_BUTTON.into();
                 *clicked = true;
+                *replay = replay_button.is_some();
+                *watch = watch_button.is_some();
             }
             Interaction::Hovered => {
                 *color = HOVERED_BUTTON.into();
@@ -566,16 +1736,208 @@ pub fn endgame(
     if *pressed {
         *pressed = false;
 
-        for (e, _s, _t) in ms_query.iter() {
-            c.entity(e).despawn();
-        }
         for e in text_entity_query.iter() {
             c.entity(e).despawn();
         }
         for e in button_entity_query.iter() {
             c.entity(e).despawn();
         }
+        for e in menu_entity_query.iter() {
+            c.entity(e).despawn();
+        }
+
+        if *replay {
+            // keep the grid sprites and the seed, run_ms resets them
+            state.set(GameState::Playing);
+        } else if *watch {
+            for (e, _s, _t) in ms_query.iter() {
+                c.entity(e).despawn();
+            }
+            match Viewer::new(game.replay.clone()) {
+                Ok(viewer) => {
+                    c.insert_resource(viewer);
+                    state.set(GameState::Replay);
+                }
+                Err(e) => {
+                    toasts.send(Toast::error(format!("Could not watch the replay: {}", e)));
+                    state.set(GameState::Intro);
+                }
+            }
+        } else {
+            for (e, _s, _t) in ms_query.iter() {
+                c.entity(e).despawn();
+            }
+            ms_info.seed = None;
+            state.set(GameState::Intro);
+        }
+    }
+}
+
+// a replay file dropped on the intro screen is opened in the viewer
+pub fn open_dropped_replay(
+    mut c: Commands,
+    mut dropped: EventReader<FileDragAndDrop>,
+    mut state: ResMut<NextState<GameState>>,
+    mut toasts: EventWriter<Toast>,
+    menu_entity_query: Query<Entity, (Or<(With<Button>, With<Text>, With<Menu>)>, Without<ToastText>)>,
+) {
+    let Some(FileDragAndDrop::DroppedFile { path_buf, .. }) = dropped.iter().last() else {
+        return;
+    };
+
+    let viewer = File::open(path_buf)
+        .map_err(|e| e.to_string())
+        .and_then(|file| Replay::read(BufReader::new(file)).map_err(|e| e.to_string()))
+        .and_then(|replay| Viewer::new(replay).map_err(|e| e.to_string()));
+    match viewer {
+        Ok(viewer) => {
+            for e in menu_entity_query.iter() {
+                c.entity(e).despawn();
+            }
+            c.insert_resource(viewer);
+            state.set(GameState::Replay);
+        }
+        Err(e) => toasts.send(Toast::error(format!("Could not open the replay: {}", e))),
+    }
+}
+
+pub fn replay_init(
+    gr: Res<GameRes>,
+    viewer: Res<Viewer>,
+    mut c: Commands,
+) {
+    spawn_cells(&mut c, &gr, viewer.ms.width, viewer.ms.height);
+
+    c.spawn(SpriteBundle {
+        sprite: Sprite {
+            color: Color::rgba(1., 0.9, 0., 0.5),
+            ..default()
+        },
+        visibility: Visibility::Hidden,
+        ..default()
+    })
+    .insert(ReplayCursor);
+
+    c.spawn(Text2dBundle {
+        text: Text::from_section(
+            "",
+            TextStyle {
+                font: gr.font.clone(),
+                font_size: INTRO_FONT_SIZE / 3.,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+        ),
+        text_anchor: Anchor::TopLeft,
+        ..default()
+    })
+    .insert(ReplayStatus);
+}
 
+pub fn run_replay(
+    time: Res<Time>,
+    gr: Res<GameRes>,
+    keys: Res<Input<KeyCode>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut viewer: ResMut<Viewer>,
+    mut state: ResMut<NextState<GameState>>,
+    mut toasts: EventWriter<Toast>,
+    mut c: Commands,
+    mut sprites: Query<(&mut Sprite, &mut Transform, &mut Handle<Image>), With<MS>>,
+    mut cursor_query: Query<(&mut Sprite, &mut Transform, &mut Visibility), (With<ReplayCursor>, Without<MS>)>,
+    mut status_query: Query<(&mut Text, &mut Transform), (With<ReplayStatus>, Without<MS>, Without<ReplayCursor>)>,
+    entity_query: Query<Entity, Or<(With<MS>, With<ReplayCursor>, With<ReplayStatus>)>>,
+) {
+    if keys.just_pressed(KeyCode::Return) {
+        for e in entity_query.iter() {
+            c.entity(e).despawn();
+        }
+        c.remove_resource::<Viewer>();
         state.set(GameState::Intro);
+        return;
+    }
+
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+
+    if keys.just_pressed(KeyCode::Space) {
+        viewer.playing = !viewer.playing;
+    }
+    if keys.just_pressed(KeyCode::Up) {
+        viewer.speed = f32::min(viewer.speed * 2., 16.);
+    }
+    if keys.just_pressed(KeyCode::Down) {
+        viewer.speed = f32::max(viewer.speed / 2., 0.25);
+    }
+    let mut broken = None;
+    if keys.just_pressed(KeyCode::Right) {
+        viewer.playing = false;
+        broken = viewer.step_forward().err();
+    }
+    if keys.just_pressed(KeyCode::Left) {
+        viewer.playing = false;
+        viewer.step_back();
+    }
+
+    if viewer.playing {
+        let clock = viewer.clock + time.delta().mul_f32(viewer.speed);
+        while broken.is_none()
+            && viewer.playing
+            && viewer.replay.records.get(viewer.position).map_or(true, |r| r.time <= clock)
+        {
+            broken = viewer.step_forward().err();
+        }
+        viewer.clock = clock;
+    }
+    if let Some(e) = broken {
+        toasts.send(Toast::error(format!("Replay stopped: {}", e)));
+    }
+
+    let ms = &viewer.ms;
+
+    let grid_max = max(ms.width, ms.height) as f32;
+    let wind_min = f32::min(window.width(), window.height());
+
+    let size = wind_min / (grid_max + 1.);
+    let size_vec = Some(Vec2::new(size, size));
+
+    let pad_x = size/2.;
+    let pad_y = size/2.;
+
+    for ((Coord { x, y }, cell), (mut s, mut t, mut i)) in ms.grid().cells().zip(&mut sprites) {
+        t.translation = Vec3::new(
+            pad_x + (x as f32 - ms.width as f32  / 2.) * size,
+            pad_y + (y as f32 - ms.height as f32 / 2.) * size,
+            0.,
+        );
+        s.custom_size = size_vec;
+        *i = gr.imgs.get(&cell_image(*cell)).unwrap().clone();
+    }
+
+    for (mut s, mut t, mut v) in &mut cursor_query {
+        match viewer.cursor() {
+            Some((x, y)) => {
+                t.translation = Vec3::new(
+                    pad_x + (x as f32 - ms.width as f32  / 2.) * size,
+                    pad_y + (y as f32 - ms.height as f32 / 2.) * size,
+                    1.,
+                );
+                s.custom_size = size_vec;
+                *v = Visibility::Visible;
+            }
+            None => *v = Visibility::Hidden,
+        }
+    }
+
+    for (mut text, mut t) in &mut status_query {
+        text.sections[0].value = format!(
+            "{} x{}  {}/{}  {:.1}s\nSpace: play/pause, Left/Right: step, Up/Down: speed, Enter: quit",
+            if viewer.playing { "Playing" } else { "Paused" },
+            viewer.speed,
+            viewer.position,
+            viewer.replay.records.len(),
+            viewer.clock.as_secs_f32(),
+        );
+        t.translation = Vec3::new(-window.width() / 2. + 10., window.height() / 2. - 10., 2.);
     }
 }
@@ -0,0 +1,476 @@
+//! A compact engine for simulations.
+//!
+//! [`BitBoard`] keeps mines, revealed cells and flags as bitsets and counts
+//! the mines around a cell from them instead of storing the numbers. It plays
+//! by the rules of [`Minesweeper`] and a builder gives the same grid to both,
+//! but there is no undo and no practice mode.
+//!
+//! ```
+//! use minesweeper::{board::Board, minesweeper::MinesweeperBuilder};
+//!
+//! let builder = MinesweeperBuilder::new(30, 16, 99).seed(7);
+//! let mut fast = builder.build_bitboard().unwrap();
+//! let mut full = builder.build().unwrap();
+//! assert_eq!(fast.open(15, 8), full.open(15, 8));
+//! assert_eq!(fast.number_of_revealed_cells(), full.number_of_revealed_cells());
+//! ```
+
+use crate::{
+    board::Board,
+    grid::Coord,
+    minesweeper::{
+        Cell, ConfigError, FirstClickPolicy, GenerationPolicy, MinesweeperBuilder, MinesweeperError, Outcome,
+    },
+};
+use rand::{seq::index, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::cmp::min;
+
+// one bit per cell, row by row
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Bits {
+    words: Vec<u64>,
+}
+
+impl Bits {
+    // a spare word lets `count` read past the last cell
+    fn new(len: usize) -> Self {
+        Bits { words: vec![0; len / 64 + 1] }
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.words[i / 64] >> (i % 64) & 1 == 1
+    }
+
+    fn set(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    fn toggle(&mut self, i: usize) {
+        self.words[i / 64] ^= 1 << (i % 64);
+    }
+
+    fn clear_all(&mut self) {
+        self.words.fill(0);
+    }
+
+    // set bits among the `len` (at most 63) starting at `start`
+    fn count(&self, start: usize, len: usize) -> u32 {
+        let (word, bit) = (start / 64, start % 64);
+        let mut bits = self.words[word] >> bit;
+        if bit + len > 64 {
+            bits |= self.words[word + 1] << (64 - bit);
+        }
+        (bits & ((1 << len) - 1)).count_ones()
+    }
+}
+
+/// A game stored as bitsets, see the [module](self) docs
+#[derive(Clone, Debug)]
+pub struct BitBoard {
+    width: usize,
+    height: usize,
+    number_of_mines: usize,
+    seed: u64,
+    generation: GenerationPolicy,
+    first_click: FirstClickPolicy,
+    mines: Bits,
+    revealed: Bits,
+    flags: Bits,
+    playing: bool,
+    won: bool,
+    first_move: bool,
+    number_of_revealed_cells: usize,
+    number_of_flagged_mines: usize,
+    number_of_flags: usize,
+    // cells waiting in the flood fill, kept to open without allocating
+    queue: Vec<usize>,
+}
+
+impl MinesweeperBuilder {
+    /// A [`BitBoard`] that gets the grid [`build`](Self::build) would give
+    pub fn build_bitboard(self) -> Result<BitBoard, ConfigError> {
+        self.validate()?;
+
+        let cells = self.width * self.height;
+        Ok(BitBoard {
+            width: self.width,
+            height: self.height,
+            number_of_mines: self.number_of_mines,
+            seed: self.seed.unwrap_or_else(rand::random),
+            generation: self.generation,
+            first_click: self.first_click,
+            mines: Bits::new(cells),
+            revealed: Bits::new(cells),
+            flags: Bits::new(cells),
+            playing: true,
+            won: true,
+            first_move: true,
+            number_of_revealed_cells: 0,
+            number_of_flagged_mines: 0,
+            number_of_flags: 0,
+            queue: vec![],
+        })
+    }
+}
+
+impl BitBoard {
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn number_of_flags(&self) -> usize {
+        self.number_of_flags
+    }
+
+    /// Starts a new game of the same size, reusing the memory of this one
+    pub fn restart(&mut self, seed: u64) {
+        self.seed = seed;
+        self.mines.clear_all();
+        self.revealed.clear_all();
+        self.flags.clear_all();
+        self.playing = true;
+        self.won = true;
+        self.first_move = true;
+        self.number_of_revealed_cells = 0;
+        self.number_of_flagged_mines = 0;
+        self.number_of_flags = 0;
+    }
+
+    /// Mines around a cell, counted from the bits of up to three rows
+    pub fn surrounds(&self, c: Coord) -> u8 {
+        let i = self.index(c);
+        let x_start = c.x.saturating_sub(1);
+        let len = min(c.x + 1, self.width - 1) - x_start + 1;
+
+        let mut count = 0;
+        for y in c.y.saturating_sub(1)..=min(c.y + 1, self.height - 1) {
+            count += self.mines.count(y * self.width + x_start, len);
+        }
+        (count - self.mines.get(i) as u32) as u8
+    }
+
+    // panics like indexing a `Grid` does
+    fn index(&self, c: Coord) -> usize {
+        if c.x >= self.width || c.y >= self.height {
+            panic!("{} is outside of the {}x{} grid", c, self.width, self.height);
+        }
+        c.y * self.width + c.x
+    }
+
+    fn coord(&self, i: usize) -> Coord {
+        Coord::new(i % self.width, i / self.width)
+    }
+
+    // calls `f` with the index of every cell touching `i`, taking the size
+    // instead of `self` so that `f` can change the board
+    fn for_each_neighbour(width: usize, height: usize, i: usize, mut f: impl FnMut(usize)) {
+        let (x, y) = (i % width, i / width);
+        for cy in y.saturating_sub(1)..=min(y + 1, height - 1) {
+            for cx in x.saturating_sub(1)..=min(x + 1, width - 1) {
+                if cx != x || cy != y {
+                    f(cy * width + cx);
+                }
+            }
+        }
+    }
+
+    fn check_action(&self, x: usize, y: usize) -> Result<usize, MinesweeperError> {
+        if x >= self.width || y >= self.height {
+            return Err(MinesweeperError::OutOfBounds { x, y });
+        }
+        if !self.playing {
+            return Err(MinesweeperError::GameOver);
+        }
+        Ok(y * self.width + x)
+    }
+
+    fn generate(&mut self, first: Coord) {
+        let mines = match self.generation {
+            GenerationPolicy::Random => self.random_mines(first),
+            // the solver works on the full engine, which is slower but rare in simulations
+            GenerationPolicy::NoGuess { .. } => {
+                let mut ms = MinesweeperBuilder::new(self.width, self.height, self.number_of_mines)
+                    .seed(self.seed)
+                    .generation(self.generation)
+                    .first_click(self.first_click)
+                    .build()
+                    .expect("the configuration was validated");
+                let _ = ms.open(first.x, first.y);
+                ms.mine_layout().into_iter().map(|c| self.index(c)).collect()
+            }
+        };
+
+        for i in mines {
+            self.mines.set(i);
+            // flags placed before the first move may land on a mine
+            if self.flags.get(i) {
+                self.number_of_flagged_mines += 1;
+            }
+        }
+    }
+
+    // draws the cells `Minesweeper` would: the same sample of the unprotected
+    // cells, taken in row order
+    fn random_mines(&self, first: Coord) -> Vec<usize> {
+        let mut protected = vec![];
+        let distance = match self.first_click {
+            FirstClickPolicy::SafeCell => Some(0),
+            FirstClickPolicy::SafeArea => Some(1),
+            FirstClickPolicy::Unprotected => None,
+        };
+        if let Some(d) = distance {
+            for y in first.y.saturating_sub(d)..=min(first.y + d, self.height - 1) {
+                for x in first.x.saturating_sub(d)..=min(first.x + d, self.width - 1) {
+                    protected.push(y * self.width + x);
+                }
+            }
+        }
+
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let candidates = self.width * self.height - protected.len();
+        index::sample(&mut rng, candidates, min(self.number_of_mines, candidates))
+            .into_iter()
+            .map(|mut i| {
+                // skip the protected cells before it
+                for &p in &protected {
+                    if p <= i {
+                        i += 1;
+                    }
+                }
+                i
+            })
+            .collect()
+    }
+
+    fn reveal(&mut self, i: usize, revealed: &mut usize) {
+        self.revealed.set(i);
+        self.number_of_revealed_cells += 1;
+        *revealed += 1;
+    }
+
+    // the same flood fill as `Minesweeper`, counting instead of listing cells
+    fn open_empty(&mut self, i: usize, revealed: &mut usize) {
+        if self.revealed.get(i) {
+            return;
+        }
+
+        let mut queue = std::mem::take(&mut self.queue);
+        self.reveal(i, revealed);
+        queue.push(i);
+
+        while let Some(i) = queue.pop() {
+            if self.surrounds(self.coord(i)) != 0 {
+                continue;
+            }
+            Self::for_each_neighbour(self.width, self.height, i, |n| {
+                if !self.revealed.get(n) {
+                    self.revealed.set(n);
+                    self.number_of_revealed_cells += 1;
+                    *revealed += 1;
+                    queue.push(n);
+                }
+            });
+        }
+        self.queue = queue;
+    }
+
+    fn check_for_win(&mut self) {
+        if self.width * self.height - self.number_of_revealed_cells == self.number_of_flagged_mines {
+            self.playing = false;
+        }
+    }
+}
+
+impl Board for BitBoard {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn number_of_mines(&self) -> usize {
+        self.number_of_mines
+    }
+
+    fn number_of_revealed_cells(&self) -> usize {
+        self.number_of_revealed_cells
+    }
+
+    fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    fn is_won(&self) -> bool {
+        !self.playing && self.won
+    }
+
+    fn cell(&self, c: Coord) -> Cell {
+        let i = self.index(c);
+        Cell {
+            surrounds: self.surrounds(c),
+            mine: self.mines.get(i),
+            flag: self.flags.get(i),
+            revealed: self.revealed.get(i),
+        }
+    }
+
+    fn open(&mut self, x: usize, y: usize) -> Result<Outcome, MinesweeperError> {
+        let i = self.check_action(x, y)?;
+        if self.flags.get(i) || self.revealed.get(i) {
+            return Ok(Outcome::Ignored);
+        }
+
+        if self.first_move {
+            self.generate(Coord::new(x, y));
+            self.first_move = false;
+        }
+
+        if self.mines.get(i) {
+            self.revealed.set(i);
+            self.playing = false;
+            self.won = false;
+            return Ok(Outcome::HitMine);
+        }
+
+        let mut revealed = 0;
+        self.open_empty(i, &mut revealed);
+        self.check_for_win();
+        Ok(Outcome::Revealed(revealed))
+    }
+
+    fn flag(&mut self, x: usize, y: usize) -> Result<Outcome, MinesweeperError> {
+        let i = self.check_action(x, y)?;
+        if self.revealed.get(i) {
+            return Ok(Outcome::Ignored);
+        }
+
+        self.flags.toggle(i);
+        let flag = self.flags.get(i);
+        match flag {
+            true => self.number_of_flags += 1,
+            false => self.number_of_flags -= 1,
+        }
+        if self.mines.get(i) {
+            match flag {
+                true => self.number_of_flagged_mines += 1,
+                false => self.number_of_flagged_mines -= 1,
+            }
+        }
+        self.check_for_win();
+
+        match flag {
+            true => Ok(Outcome::Flagged),
+            false => Ok(Outcome::Unflagged),
+        }
+    }
+
+    fn chord(&mut self, x: usize, y: usize) -> Result<Outcome, MinesweeperError> {
+        let i = self.check_action(x, y)?;
+        let surrounds = self.surrounds(Coord::new(x, y));
+        if !self.revealed.get(i) || self.mines.get(i) || surrounds == 0 {
+            return Ok(Outcome::Ignored);
+        }
+
+        let mut flags = 0;
+        Self::for_each_neighbour(self.width, self.height, i, |n| flags += self.flags.get(n) as u8);
+        if flags != surrounds {
+            return Ok(Outcome::Ignored);
+        }
+
+        let mut neighbours = Vec::with_capacity(8);
+        Self::for_each_neighbour(self.width, self.height, i, |n| neighbours.push(n));
+
+        let mut revealed = 0;
+        let mut hit_mine = false;
+        for n in neighbours {
+            if self.flags.get(n) || self.revealed.get(n) {
+                continue;
+            }
+            if self.mines.get(n) {
+                self.revealed.set(n);
+                revealed += 1;
+                hit_mine = true;
+            } else {
+                self.open_empty(n, &mut revealed);
+            }
+        }
+
+        if hit_mine {
+            self.playing = false;
+            self.won = false;
+            return Ok(Outcome::HitMine);
+        }
+
+        self.check_for_win();
+        match revealed {
+            0 => Ok(Outcome::Ignored),
+            n => Ok(Outcome::Revealed(n)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minesweeper::Action;
+    use rand::Rng;
+
+    #[test]
+    fn plays_like_minesweeper() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        for seed in 0..200 {
+            let (width, height) = (rng.gen_range(1..40), rng.gen_range(1..40));
+            let mines = rng.gen_range(0..=width * height - 1);
+            let policies = [FirstClickPolicy::SafeCell, FirstClickPolicy::SafeArea, FirstClickPolicy::Unprotected];
+            let first_click = policies[seed as usize % 3];
+            let builder = MinesweeperBuilder::new(width, height, mines).seed(seed).first_click(first_click);
+
+            let (mut full, mut fast) = match (builder.build(), builder.build_bitboard()) {
+                (Ok(full), Ok(fast)) => (full, fast),
+                // a safe area leaves fewer cells for the mines
+                (full, fast) => {
+                    assert_eq!(full.err(), fast.err());
+                    continue;
+                }
+            };
+            for _ in 0..50 {
+                let (x, y) = (rng.gen_range(0..width + 1), rng.gen_range(0..height));
+                let action = match rng.gen_range(0..5) {
+                    0 => Action::Flag(x, y),
+                    1 => Action::Chord(x, y),
+                    _ => Action::Open(x, y),
+                };
+                assert_eq!(fast.play(action), full.play(action), "seed {}, {:?}", seed, action);
+            }
+
+            assert_eq!((fast.is_playing(), fast.is_won()), (full.is_playing(), full.is_won()));
+            for c in full.grid().coords() {
+                assert_eq!(fast.cell(c), Board::cell(&full, c));
+            }
+        }
+    }
+
+    #[test]
+    fn counts_neighbours_across_words() {
+        // rows of 70 cells put the neighbours of (63, 1) into two words
+        let mut board = MinesweeperBuilder::new(70, 3, 0).build_bitboard().unwrap();
+        for x in 62..=64 {
+            for y in 0..3 {
+                board.mines.set(y * 70 + x);
+            }
+        }
+        assert_eq!(board.surrounds(Coord::new(63, 1)), 8);
+        assert_eq!(board.surrounds(Coord::new(65, 1)), 3);
+        assert_eq!(board.surrounds(Coord::new(69, 2)), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "(70, 0) is outside of the 70x3 grid")]
+    fn cells_outside_of_the_board_panic() {
+        let board = MinesweeperBuilder::new(70, 3, 0).build_bitboard().unwrap();
+        board.cell(Coord::new(70, 0));
+    }
+}
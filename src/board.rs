@@ -0,0 +1,77 @@
+//! Rules shared by the game engines.
+
+use crate::{
+    grid::Coord,
+    minesweeper::{Action, Cell, Minesweeper, MinesweeperError, Outcome},
+};
+
+/// A game played through its actions, so simulations can run on any engine:
+/// [`Minesweeper`] with its undo history or the compact
+/// [`BitBoard`](crate::bitboard::BitBoard)
+pub trait Board {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn number_of_mines(&self) -> usize;
+    /// Revealed cells without a mine
+    fn number_of_revealed_cells(&self) -> usize;
+    fn is_playing(&self) -> bool;
+    /// The game is over and was won
+    fn is_won(&self) -> bool;
+    /// Everything known about a cell, there are no mines before the first move.
+    /// Panics if `c` is outside of the board
+    fn cell(&self, c: Coord) -> Cell;
+
+    fn open(&mut self, x: usize, y: usize) -> Result<Outcome, MinesweeperError>;
+    fn flag(&mut self, x: usize, y: usize) -> Result<Outcome, MinesweeperError>;
+    fn chord(&mut self, x: usize, y: usize) -> Result<Outcome, MinesweeperError>;
+
+    fn play(&mut self, action: Action) -> Result<Outcome, MinesweeperError> {
+        match action {
+            Action::Open(x, y) => self.open(x, y),
+            Action::Flag(x, y) => self.flag(x, y),
+            Action::Chord(x, y) => self.chord(x, y),
+        }
+    }
+}
+
+impl Board for Minesweeper {
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn number_of_mines(&self) -> usize {
+        Minesweeper::number_of_mines(self)
+    }
+
+    fn number_of_revealed_cells(&self) -> usize {
+        Minesweeper::number_of_revealed_cells(self)
+    }
+
+    fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    fn is_won(&self) -> bool {
+        !self.playing && self.won
+    }
+
+    fn cell(&self, c: Coord) -> Cell {
+        self.grid()[c]
+    }
+
+    fn open(&mut self, x: usize, y: usize) -> Result<Outcome, MinesweeperError> {
+        Minesweeper::open(self, x, y)
+    }
+
+    fn flag(&mut self, x: usize, y: usize) -> Result<Outcome, MinesweeperError> {
+        Minesweeper::flag(self, x, y)
+    }
+
+    fn chord(&mut self, x: usize, y: usize) -> Result<Outcome, MinesweeperError> {
+        Minesweeper::chord(self, x, y)
+    }
+}
@@ -0,0 +1,279 @@
+//! Flat storage for the cells of a board.
+//!
+//! A [`Grid`] keeps its cells row by row in a single buffer and is indexed by
+//! [`Coord`], `x` being the column and `y` the row.
+//!
+//! ```
+//! use minesweeper::grid::{Coord, Grid};
+//!
+//! let mut grid = Grid::new(3, 2, 0);
+//! grid[Coord::new(2, 1)] = 5;
+//! assert_eq!(grid.row(1), &[0, 0, 5]);
+//! assert_eq!(grid.column(2).copied().collect::<Vec<_>>(), vec![0, 5]);
+//! assert_eq!(grid.neighbours(Coord::new(0, 0)).count(), 3);
+//! ```
+
+use std::{
+    cmp::min,
+    fmt,
+    ops::{Index, IndexMut},
+    slice,
+};
+
+/// Position of a cell
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Coord {
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Coord {
+    pub const fn new(x: usize, y: usize) -> Self {
+        Coord { x, y }
+    }
+}
+
+impl From<(usize, usize)> for Coord {
+    fn from((x, y): (usize, usize)) -> Self {
+        Coord { x, y }
+    }
+}
+
+impl From<Coord> for (usize, usize) {
+    fn from(c: Coord) -> Self {
+        (c.x, c.y)
+    }
+}
+
+impl fmt::Display for Coord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+/// Cells of a rectangular board in one contiguous buffer
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, value: T) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![value; width * height],
+        }
+    }
+
+    /// Sets every cell to `value`, keeping the buffer
+    pub fn fill(&mut self, value: T) {
+        self.cells.fill(value);
+    }
+}
+
+impl<T> Grid<T> {
+    /// A grid with the value of every cell computed from its position
+    pub fn from_fn(width: usize, height: usize, mut f: impl FnMut(Coord) -> T) -> Self {
+        let cells = (0..height).flat_map(|y| (0..width).map(move |x| Coord { x, y })).map(&mut f).collect();
+        Grid { width, height, cells }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Number of cells
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn contains(&self, c: Coord) -> bool {
+        c.x < self.width && c.y < self.height
+    }
+
+    pub fn get(&self, c: Coord) -> Option<&T> {
+        match self.contains(c) {
+            true => Some(&self.cells[c.y * self.width + c.x]),
+            false => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, c: Coord) -> Option<&mut T> {
+        match self.contains(c) {
+            true => Some(&mut self.cells[c.y * self.width + c.x]),
+            false => None,
+        }
+    }
+
+    /// Position of the cell at `index` of the buffer
+    pub fn coord(&self, index: usize) -> Coord {
+        Coord::new(index % self.width, index / self.width)
+    }
+
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    pub fn row_mut(&mut self, y: usize) -> &mut [T] {
+        &mut self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    /// Rows from the top
+    pub fn rows(&self) -> impl ExactSizeIterator<Item = &[T]> {
+        // a grid without columns has no rows either
+        self.cells.chunks_exact(self.width.max(1))
+    }
+
+    /// Cells of column `x` from the top
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        assert!(x < self.width, "column {} is outside of a grid of width {}", x, self.width);
+        self.cells[x..].iter().step_by(self.width)
+    }
+
+    /// Cells row by row
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.cells.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, T> {
+        self.cells.iter_mut()
+    }
+
+    /// Every position row by row
+    pub fn coords(&self) -> impl Iterator<Item = Coord> {
+        let width = self.width;
+        (0..self.len()).map(move |i| Coord::new(i % width, i / width))
+    }
+
+    /// Cells row by row with their positions
+    pub fn cells(&self) -> impl Iterator<Item = (Coord, &T)> {
+        self.coords().zip(self.cells.iter())
+    }
+
+    /// The up to 8 positions touching `c`
+    pub fn neighbours(&self, c: Coord) -> Neighbours {
+        self.around(c, 1)
+    }
+
+    /// Positions at most `distance` cells away from `c` in both directions,
+    /// without `c` itself. There are none around a position outside of the grid
+    pub fn around(&self, c: Coord, distance: usize) -> Neighbours {
+        if !self.contains(c) {
+            return Neighbours { center: c, x_start: 0, x_end: 0, y_end: 0, x: 0, y: 1 };
+        }
+        let x_start = c.x.saturating_sub(distance);
+        let y_start = c.y.saturating_sub(distance);
+        Neighbours {
+            center: c,
+            x_start,
+            x_end: min(c.x.saturating_add(distance), self.width - 1),
+            y_end: min(c.y.saturating_add(distance), self.height - 1),
+            x: x_start,
+            y: y_start,
+        }
+    }
+}
+
+impl<T> Index<Coord> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, c: Coord) -> &T {
+        match self.get(c) {
+            Some(cell) => cell,
+            None => panic!("{} is outside of the {}x{} grid", c, self.width, self.height),
+        }
+    }
+}
+
+impl<T> IndexMut<Coord> for Grid<T> {
+    fn index_mut(&mut self, c: Coord) -> &mut T {
+        let (width, height) = (self.width, self.height);
+        match self.get_mut(c) {
+            Some(cell) => cell,
+            None => panic!("{} is outside of the {}x{} grid", c, width, height),
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Grid<T> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.cells.iter()
+    }
+}
+
+/// Positions around a cell row by row, see [`Grid::around`]
+#[derive(Clone, Debug)]
+pub struct Neighbours {
+    center: Coord,
+    x_start: usize,
+    x_end: usize,
+    y_end: usize,
+    x: usize,
+    y: usize,
+}
+
+impl Iterator for Neighbours {
+    type Item = Coord;
+
+    fn next(&mut self) -> Option<Coord> {
+        while self.y <= self.y_end {
+            let c = Coord::new(self.x, self.y);
+            if self.x == self.x_end {
+                self.x = self.x_start;
+                self.y += 1;
+            } else {
+                self.x += 1;
+            }
+            if c != self.center {
+                return Some(c);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighbours_stay_inside_the_grid() {
+        let grid = Grid::new(4, 3, ());
+        let around = |x, y| grid.neighbours(Coord::new(x, y)).map(<(usize, usize)>::from).collect::<Vec<_>>();
+
+        assert_eq!(around(0, 0), vec![(1, 0), (0, 1), (1, 1)]);
+        assert_eq!(around(3, 2), vec![(2, 1), (3, 1), (2, 2)]);
+        assert_eq!(around(1, 1).len(), 8);
+        assert_eq!(grid.around(Coord::new(0, 0), 2).count(), 8);
+        assert_eq!(Grid::new(1, 1, ()).neighbours(Coord::new(0, 0)).count(), 0);
+        assert_eq!(grid.neighbours(Coord::new(10, 0)).count(), 0);
+        assert_eq!(grid.around(Coord::new(0, 3), 2).count(), 0);
+    }
+
+    #[test]
+    fn rows_and_columns_share_the_buffer() {
+        let grid = Grid::from_fn(3, 2, |c| c.y * 10 + c.x);
+
+        assert_eq!(grid.rows().collect::<Vec<_>>(), vec![&[0, 1, 2][..], &[10, 11, 12][..]]);
+        assert_eq!(grid.column(1).copied().collect::<Vec<_>>(), vec![1, 11]);
+        assert_eq!(grid.coord(4), Coord::new(1, 1));
+        assert_eq!(grid.get(Coord::new(3, 0)), None);
+        assert_eq!(grid.cells().nth(5), Some((Coord::new(2, 1), &12)));
+    }
+}
@@ -0,0 +1,225 @@
+//! Mine layouts in the formats shared by other Minesweeper clones.
+//!
+//! - [`LayoutFormat::Text`]: one line per row, `*` for a mine and `.` for a
+//!   safe cell
+//! - [`LayoutFormat::Mbf`]: width and height bytes, the number of mines as a
+//!   big-endian `u16`, then an `x`, `y` byte pair for every mine
+//!
+//! A loaded game has its mines placed already, the first click is not protected.
+
+use crate::{
+    grid::Coord,
+    minesweeper::{ConfigError, Minesweeper},
+};
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Read, Write},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LayoutFormat {
+    Text,
+    Mbf,
+}
+
+#[derive(Debug)]
+pub enum LayoutError {
+    Io(io::Error),
+    /// The line with the given number (starting from 1) is not a row of the grid
+    Text { line: usize },
+    /// The data is shorter or longer than its mines need, or has a mine twice
+    Corrupted,
+    /// The grid or the number of mines does not fit into the MBF fields
+    TooLarge,
+    /// The grid has no mines before the first move
+    NotGenerated,
+    Config(ConfigError),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::Io(e) => write!(f, "could not read the layout: {}", e),
+            LayoutError::Text { line } => write!(f, "invalid layout row on line {}", line),
+            LayoutError::Corrupted => write!(f, "layout length or mines are inconsistent"),
+            LayoutError::TooLarge => write!(f, "grid is too large for the MBF format"),
+            LayoutError::NotGenerated => write!(f, "mines are placed on the first move"),
+            LayoutError::Config(e) => write!(f, "invalid layout: {}", e),
+        }
+    }
+}
+
+impl Error for LayoutError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LayoutError::Io(e) => Some(e),
+            LayoutError::Config(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LayoutError {
+    fn from(e: io::Error) -> Self {
+        LayoutError::Io(e)
+    }
+}
+
+impl From<ConfigError> for LayoutError {
+    fn from(e: ConfigError) -> Self {
+        LayoutError::Config(e)
+    }
+}
+
+impl Minesweeper {
+    /// A game on the grid read from `reader`
+    ///
+    /// ```
+    /// use minesweeper::{grid::Coord, layout::LayoutFormat, minesweeper::Minesweeper};
+    ///
+    /// let ms = Minesweeper::read_layout("*..\n...\n..*\n".as_bytes(), LayoutFormat::Text).unwrap();
+    /// assert_eq!(ms.number_of_mines(), 2);
+    /// assert_eq!(ms.grid()[Coord::new(1, 1)].surrounds, 2);
+    /// ```
+    pub fn read_layout<R: Read>(mut reader: R, format: LayoutFormat) -> Result<Minesweeper, LayoutError> {
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+
+        match format {
+            LayoutFormat::Text => read_text(&data),
+            LayoutFormat::Mbf => read_mbf(&data),
+        }
+    }
+
+    pub fn write_layout<W: Write>(&self, mut writer: W, format: LayoutFormat) -> Result<(), LayoutError> {
+        let mines = self.mine_layout();
+        if mines.is_empty() && self.number_of_mines() > 0 {
+            return Err(LayoutError::NotGenerated);
+        }
+
+        match format {
+            LayoutFormat::Text => {
+                for row in self.grid().rows() {
+                    let line: String = row.iter().map(|cell| if cell.mine { '*' } else { '.' }).collect();
+                    writeln!(writer, "{}", line)?;
+                }
+            }
+            LayoutFormat::Mbf => {
+                let (Ok(width), Ok(height), Ok(count)) =
+                    (u8::try_from(self.width), u8::try_from(self.height), u16::try_from(mines.len()))
+                else {
+                    return Err(LayoutError::TooLarge);
+                };
+
+                let mut data = vec![width, height];
+                data.extend(count.to_be_bytes());
+                for c in mines {
+                    // both fit since the width and height do
+                    data.extend([c.x as u8, c.y as u8]);
+                }
+                writer.write_all(&data)?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+fn read_text(data: &[u8]) -> Result<Minesweeper, LayoutError> {
+    let text = String::from_utf8_lossy(data);
+    let mut mines = vec![];
+    let mut width = None;
+    let mut height = 0;
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut length = 0;
+        for (x, c) in line.chars().enumerate() {
+            match c {
+                '*' => mines.push(Coord::new(x, height)),
+                '.' => {}
+                _ => return Err(LayoutError::Text { line: i + 1 }),
+            }
+            length += 1;
+        }
+        if *width.get_or_insert(length) != length {
+            return Err(LayoutError::Text { line: i + 1 });
+        }
+        height += 1;
+    }
+
+    Ok(Minesweeper::from_mine_layout(width.unwrap_or(0), height, &mines)?)
+}
+
+fn read_mbf(data: &[u8]) -> Result<Minesweeper, LayoutError> {
+    let [width, height, high, low, cells @ ..] = data else {
+        return Err(LayoutError::Corrupted);
+    };
+    let count = u16::from_be_bytes([*high, *low]) as usize;
+    if cells.len() != 2 * count {
+        return Err(LayoutError::Corrupted);
+    }
+
+    let mut mines: Vec<Coord> = cells.chunks(2).map(|c| Coord::new(c[0] as usize, c[1] as usize)).collect();
+    // the header would count a repeated mine that the grid can't hold
+    mines.sort_unstable();
+    mines.dedup();
+    if mines.len() != count {
+        return Err(LayoutError::Corrupted);
+    }
+    Ok(Minesweeper::from_mine_layout(*width as usize, *height as usize, &mines)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minesweeper::MinesweeperBuilder;
+
+    fn mbf(ms: &Minesweeper) -> Vec<u8> {
+        let mut data = vec![];
+        ms.write_layout(&mut data, LayoutFormat::Mbf).unwrap();
+        data
+    }
+
+    #[test]
+    fn mbf_round_trip() {
+        let mut ms = MinesweeperBuilder::new(30, 16, 99).seed(4).build().unwrap();
+        ms.open(0, 0).unwrap();
+
+        let data = mbf(&ms);
+        assert_eq!(data[..4], [30, 16, 0, 99]);
+        assert_eq!(data.len(), 4 + 2 * 99);
+        let loaded = Minesweeper::read_layout(&data[..], LayoutFormat::Mbf).unwrap();
+        assert_eq!((loaded.width, loaded.height), (30, 16));
+        assert_eq!(loaded.mine_layout(), ms.mine_layout());
+    }
+
+    #[test]
+    fn rejects_what_mbf_can_not_hold() {
+        let mut ms = MinesweeperBuilder::new(300, 2, 1).seed(0).build().unwrap();
+        let mut data = vec![];
+        assert!(matches!(ms.write_layout(&mut data, LayoutFormat::Mbf), Err(LayoutError::NotGenerated)));
+        ms.open(0, 0).unwrap();
+        assert!(matches!(ms.write_layout(&mut data, LayoutFormat::Mbf), Err(LayoutError::TooLarge)));
+    }
+
+    #[test]
+    fn rejects_broken_mbf() {
+        let read = |data: &[u8]| Minesweeper::read_layout(data, LayoutFormat::Mbf).err();
+        let ms = Minesweeper::from_mine_layout(4, 4, &[Coord::new(1, 2), Coord::new(3, 3)]).unwrap();
+        let data = mbf(&ms);
+
+        assert!(matches!(read(&data[..3]), Some(LayoutError::Corrupted)));
+        assert!(matches!(read(&data[..data.len() - 1]), Some(LayoutError::Corrupted)));
+        assert!(matches!(read(&[4, 4, 0, 2, 1, 2, 1, 2]), Some(LayoutError::Corrupted)));
+        assert!(matches!(
+            read(&[4, 4, 0, 1, 4, 0]),
+            Some(LayoutError::Config(ConfigError::MineOutOfBounds { x: 4, y: 0 }))
+        ));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::minesweeper::Difficulty;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{Read, Write},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Number of entries kept for every difficulty
+pub const TOP: usize = 10;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// Times are only compared between equal difficulties
+    pub difficulty: Difficulty,
+    pub name: String,
+    pub time: Duration,
+    pub bbbv_per_second: f64,
+    /// Seconds since the Unix epoch
+    pub date: u64,
+}
+
+impl Entry {
+    /// An entry dated now
+    pub fn new(difficulty: Difficulty, name: String, time: Duration, bbbv_per_second: f64) -> Self {
+        Entry {
+            difficulty,
+            name,
+            time,
+            bbbv_per_second,
+            date: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()),
+        }
+    }
+
+    /// The date as `YYYY-MM-DD` in UTC
+    pub fn date_string(&self) -> String {
+        // civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+        let days = (self.date / 86_400) as i64 + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+/// Best times of every difficulty
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Leaderboard {
+    entries: Vec<Entry>,
+}
+
+impl Leaderboard {
+    pub fn load<R: Read>(reader: R) -> Result<Leaderboard, serde_json::Error> {
+        let mut leaderboard: Leaderboard = serde_json::from_reader(reader)?;
+        leaderboard.entries.sort_by(|a, b| a.difficulty.cmp(&b.difficulty).then(a.time.cmp(&b.time)));
+        Ok(leaderboard)
+    }
+
+    pub fn save<W: Write>(&self, writer: W) -> Result<(), serde_json::Error> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+
+    /// Adds the entry if it is among the best [`TOP`] of its difficulty,
+    /// returns its place starting from 0
+    pub fn insert(&mut self, entry: Entry) -> Option<usize> {
+        let difficulty = entry.difficulty;
+        let place = self.top(difficulty).iter().take_while(|e| e.time <= entry.time).count();
+        if place >= TOP {
+            return None;
+        }
+
+        self.entries.push(entry);
+        self.entries.sort_by(|a, b| a.difficulty.cmp(&b.difficulty).then(a.time.cmp(&b.time)));
+
+        // drop the entry pushed out of the top
+        let mut kept = 0;
+        self.entries.retain(|e| {
+            if e.difficulty != difficulty {
+                return true;
+            }
+            kept += 1;
+            kept <= TOP
+        });
+        Some(place)
+    }
+
+    /// Changes the name of the entry at `place` of a difficulty, as returned by
+    /// [`insert`](Self::insert)
+    pub fn rename(&mut self, difficulty: Difficulty, place: usize, name: String) {
+        if let Some(entry) = self.entries.iter_mut().filter(|e| e.difficulty == difficulty).nth(place) {
+            entry.name = name;
+        }
+    }
+
+    /// Best entries of a difficulty, fastest first
+    pub fn top(&self, difficulty: Difficulty) -> Vec<&Entry> {
+        self.entries.iter().filter(|e| e.difficulty == difficulty).collect()
+    }
+
+    /// Every difficulty with its best entry, presets first
+    pub fn best(&self) -> Vec<&Entry> {
+        let mut best: Vec<&Entry> = vec![];
+        for entry in &self.entries {
+            if best.last().map_or(true, |b| b.difficulty != entry.difficulty) {
+                best.push(entry);
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPERT: Difficulty = Difficulty::Expert;
+
+    fn entry(difficulty: Difficulty, secs: u64) -> Entry {
+        Entry::new(difficulty, "player".to_owned(), Duration::from_secs(secs), 1.)
+    }
+
+    #[test]
+    fn keeps_the_fastest_times_per_difficulty() {
+        let mut leaderboard = Leaderboard::default();
+        for secs in (1..=TOP as u64).rev() {
+            leaderboard.insert(entry(EXPERT, secs * 10));
+        }
+        let custom = Difficulty::from_size(20, 20, 50);
+        assert_eq!(leaderboard.insert(entry(custom, 500)), Some(0));
+
+        assert_eq!(leaderboard.insert(entry(EXPERT, 15)), Some(1));
+        assert_eq!(leaderboard.insert(entry(EXPERT, 200)), None);
+
+        let top = leaderboard.top(EXPERT);
+        assert_eq!(top.len(), TOP);
+        assert_eq!(top[1].time, Duration::from_secs(15));
+        assert_eq!(top[TOP - 1].time, Duration::from_secs(90));
+        assert_eq!(leaderboard.best().len(), 2);
+
+        leaderboard.rename(EXPERT, 1, "new".to_owned());
+        assert_eq!(leaderboard.top(EXPERT)[1].name, "new");
+        assert_eq!(leaderboard.top(custom)[0].name, "player");
+    }
+
+    #[test]
+    fn formats_dates() {
+        let mut e = entry(EXPERT, 1);
+        e.date = 0;
+        assert_eq!(e.date_string(), "1970-01-01");
+        e.date = 951_782_400;
+        assert_eq!(e.date_string(), "2000-02-29");
+    }
+}
@@ -1,2 +1,15 @@
+pub mod grid;
 pub mod minesweeper;
+pub mod solver;
+pub mod probability;
+pub mod replay;
+pub mod layout;
+pub mod metrics;
+pub mod board;
+pub mod bitboard;
+#[cfg(feature = "serde")]
+pub mod save;
+#[cfg(feature = "serde")]
+pub mod leaderboard;
+#[cfg(feature = "serde")]
 pub mod app_parameters;
@@ -11,6 +11,9 @@ fn main() {
     App::new()
         .init_resource::<MSInfo>()
         .init_resource::<GameWon>()
+        .init_resource::<Game>()
+        .init_resource::<PlayerName>()
+        .add_event::<Toast>()
         .insert_resource(Msaa::Off)
         .insert_resource(ClearColor(Color::rgb(0., 0., 0.)))
         .add_plugins(DefaultPlugins.set(WindowPlugin {
@@ -21,12 +24,24 @@ fn main() {
             default_sampler: ImageSampler::nearest_descriptor(),
         }))
         .add_system(close_on_esc)
+        .add_system(save_on_exit.in_base_set(CoreSet::Last))
+        .add_system(show_toasts)
         .add_startup_system(startup)
         .add_state::<GameState>()
             .add_system(init.in_schedule(OnEnter(GameState::Intro)))
-            .add_system(init_ms.in_set(OnUpdate(GameState::Intro)))
+            .add_system(choose_preset.in_set(OnUpdate(GameState::Intro)).before(edit_fields))
+            .add_system(edit_fields.in_set(OnUpdate(GameState::Intro)))
+            .add_system(init_ms.in_set(OnUpdate(GameState::Intro)).after(edit_fields))
+            .add_system(open_dropped_replay.in_set(OnUpdate(GameState::Intro)))
+            .add_system(toggle_leaderboard.in_set(OnUpdate(GameState::Intro)))
             .add_system(run_ms.in_set(OnUpdate(GameState::Playing)))
+            .add_system(hud_init.in_schedule(OnEnter(GameState::Playing)))
+            .add_system(update_hud.in_set(OnUpdate(GameState::Playing)).after(run_ms))
+            .add_system(hud_exit.in_schedule(OnExit(GameState::Playing)))
             .add_system(endgame_init.in_schedule(OnEnter(GameState::Endgame)))
             .add_system(endgame.in_set(OnUpdate(GameState::Endgame)))
+            .add_system(edit_record_name.in_set(OnUpdate(GameState::Endgame)))
+            .add_system(replay_init.in_schedule(OnEnter(GameState::Replay)))
+            .add_system(run_replay.in_set(OnUpdate(GameState::Replay)))
         .run();
 }
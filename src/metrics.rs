@@ -0,0 +1,178 @@
+use crate::{
+    grid::{Coord, Grid},
+    minesweeper::Minesweeper,
+};
+use std::time::Duration;
+
+/// How hard a generated grid is, independent of how it was played
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BoardMetrics {
+    /// 3BV: the least number of clicks that clears the grid, one for every
+    /// opening and one for every number that does not border an opening
+    pub bbbv: usize,
+    /// Connected areas of zeros, each cleared by a single click
+    pub openings: usize,
+    /// Connected groups of numbers that do not border an opening
+    pub islands: usize,
+}
+
+/// How well a game was played
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct GameStats {
+    pub board: BoardMetrics,
+    /// 3BV of the cleared part of the grid, less than the board's one for a lost game
+    pub solved_bbbv: usize,
+    /// Opens, flags and chords, including the ones that changed nothing
+    pub clicks: usize,
+    pub time: Duration,
+}
+
+impl GameStats {
+    pub fn bbbv_per_second(&self) -> f64 {
+        match self.time.is_zero() {
+            true => 0.,
+            false => self.solved_bbbv as f64 / self.time.as_secs_f64(),
+        }
+    }
+
+    /// Solved 3BV per click, known as IOE in other clones
+    pub fn efficiency(&self) -> f64 {
+        match self.clicks {
+            0 => 0.,
+            clicks => self.solved_bbbv as f64 / clicks as f64,
+        }
+    }
+}
+
+// what every safe cell belongs to when the grid is cleared
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Region {
+    Mine,
+    Opening(usize),
+    // a number next to an opening, cleared with it
+    Border,
+    Island,
+}
+
+/// 3BV, openings and islands of a grid, the grid must already have its mines
+pub fn board_metrics(ms: &Minesweeper) -> BoardMetrics {
+    let (metrics, _) = regions(ms);
+    metrics
+}
+
+pub fn game_stats(ms: &Minesweeper, clicks: usize, time: Duration) -> GameStats {
+    let (board, regions) = regions(ms);
+
+    let mut solved_openings = vec![false; board.openings];
+    let mut solved_bbbv = 0;
+    for (region, cell) in regions.iter().zip(ms.grid()) {
+        if !cell.revealed {
+            continue;
+        }
+        match *region {
+            Region::Opening(i) => solved_openings[i] = true,
+            Region::Island => solved_bbbv += 1,
+            Region::Mine | Region::Border => {}
+        }
+    }
+    solved_bbbv += solved_openings.iter().filter(|&&solved| solved).count();
+
+    GameStats {
+        board,
+        solved_bbbv,
+        clicks,
+        time,
+    }
+}
+
+fn regions(ms: &Minesweeper) -> (BoardMetrics, Grid<Region>) {
+    let grid = ms.grid();
+    let mut metrics = BoardMetrics::default();
+
+    let is_zero = |c: Coord| !grid[c].mine && grid[c].surrounds == 0;
+    let mut regions = Grid::from_fn(ms.width, ms.height, |c| {
+        if grid[c].mine {
+            Region::Mine
+        } else if !is_zero(c) && !grid.neighbours(c).any(is_zero) {
+            Region::Island
+        } else {
+            Region::Border
+        }
+    });
+
+    let mut labelled = Grid::new(ms.width, ms.height, false);
+    for c in grid.coords() {
+        if labelled[c] || regions[c] == Region::Mine || regions[c] == Region::Border && !is_zero(c) {
+            continue;
+        }
+
+        // zeros spread to zeros, isolated numbers to isolated numbers
+        let opening = is_zero(c);
+        if opening {
+            metrics.openings += 1;
+        } else {
+            metrics.islands += 1;
+        }
+
+        let mut stack = vec![c];
+        labelled[c] = true;
+        while let Some(c) = stack.pop() {
+            if opening {
+                regions[c] = Region::Opening(metrics.openings - 1);
+            }
+            for n in grid.neighbours(c) {
+                let joins = match opening {
+                    true => is_zero(n),
+                    false => regions[n] == Region::Island,
+                };
+                if joins && !labelled[n] {
+                    labelled[n] = true;
+                    stack.push(n);
+                }
+            }
+        }
+    }
+
+    let isolated = regions.iter().filter(|&&r| r == Region::Island).count();
+    metrics.bbbv = metrics.openings + isolated;
+    (metrics, regions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_openings_and_islands() {
+        let ms: Minesweeper = "
+            * 2 * 1 0
+            1 2 1 1 0
+            0 0 0 0 0
+        ".parse().unwrap();
+
+        // the 2 between the mines is the only number not next to the opening
+        let metrics = board_metrics(&ms);
+        assert_eq!(metrics, BoardMetrics { bbbv: 2, openings: 1, islands: 1 });
+
+        // touching numbers form a single island but each takes a click
+        let ms: Minesweeper = "* 1 1 *".parse().unwrap();
+        assert_eq!(board_metrics(&ms), BoardMetrics { bbbv: 2, openings: 0, islands: 1 });
+    }
+
+    #[test]
+    fn lost_game_counts_only_solved_clicks() {
+        let ms: Minesweeper = "
+            0 0 1 *
+            0 0 1 .
+            0 0 1 .
+            0 0 . X
+        ".parse().unwrap();
+
+        let stats = game_stats(&ms, 2, Duration::from_secs(2));
+        assert_eq!(stats.board, BoardMetrics { bbbv: 3, openings: 1, islands: 1 });
+        assert_eq!(stats.solved_bbbv, 1);
+        assert_eq!(stats.bbbv_per_second(), 0.5);
+        // unsolved 3BV does not count for a lost game
+        assert_eq!(stats.efficiency(), 0.5);
+    }
+}
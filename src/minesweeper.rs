@@ -1,12 +1,21 @@
-use rand::seq::SliceRandom;
+use crate::{
+    grid::{Coord, Grid},
+    solver,
+};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use std::{
     cmp::{min, max},
-    fmt
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    str::FromStr,
 };
 
 const MINE_COUNT_DISPLAY: &[char] = &['0','1','2','3','4','5','6','7','8'];
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cell {
     pub surrounds: u8,
     pub mine: bool,
@@ -28,16 +37,341 @@ impl fmt::Display for Cell {
     }
 }
 
+/// What an action did to the grid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The given number of cells were revealed
+    Revealed(usize),
+    /// A mine was opened and the game is lost
+    HitMine,
+    Flagged,
+    Unflagged,
+    /// Nothing changed, e.g. opening a flagged cell or flagging a revealed one
+    Ignored,
+}
+
+/// A move of the player, with the coordinates of the cell it was made on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Action {
+    Open(usize, usize),
+    Flag(usize, usize),
+    Chord(usize, usize),
+}
+
+impl Action {
+    pub fn position(&self) -> (usize, usize) {
+        match *self {
+            Action::Open(x, y) | Action::Flag(x, y) | Action::Chord(x, y) => (x, y),
+        }
+    }
+}
+
+// what is needed to take an action back
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Step {
+    action: Action,
+    revealed: Vec<Coord>,
+    // the action was the first move and generated the grid
+    generated: bool,
+    playing: bool,
+    won: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MinesweeperError {
+    /// The coordinates are outside of the grid
+    OutOfBounds { x: usize, y: usize },
+    /// The game has already been won or lost
+    GameOver,
+}
+
+impl fmt::Display for MinesweeperError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MinesweeperError::OutOfBounds { x, y } => write!(f, "cell ({}, {}) is out of bounds", x, y),
+            MinesweeperError::GameOver => write!(f, "the game is over"),
+        }
+    }
+}
+
+impl Error for MinesweeperError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// Width or height is zero
+    EmptyGrid { width: usize, height: usize },
+    /// There is no room for the mines outside of the cells kept safe on the first click
+    TooManyMines { mines: usize, max: usize },
+    /// A no-guess policy with no attempts to generate a grid
+    NoAttempts,
+    /// A mine of a given layout is outside of the grid
+    MineOutOfBounds { x: usize, y: usize },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::EmptyGrid { width, height } => {
+                write!(f, "grid of size {}x{} has no cells", width, height)
+            }
+            ConfigError::TooManyMines { mines, max } => {
+                write!(f, "{} mines do not fit, at most {} are allowed", mines, max)
+            }
+            ConfigError::NoAttempts => write!(f, "no-guess generation needs at least one attempt"),
+            ConfigError::MineOutOfBounds { x, y } => write!(f, "mine ({}, {}) is out of bounds", x, y),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseBoardError {
+    /// There are no rows
+    Empty,
+    /// The row on the given line (starting from 1) is not as long as the first one
+    UnevenRow { line: usize },
+    InvalidChar { line: usize, c: char },
+    /// A revealed number does not match the mines around it
+    WrongNumber { x: usize, y: usize },
+}
+
+impl fmt::Display for ParseBoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseBoardError::Empty => write!(f, "board has no rows"),
+            ParseBoardError::UnevenRow { line } => write!(f, "row on line {} has a different length", line),
+            ParseBoardError::InvalidChar { line, c } => write!(f, "unknown cell '{}' on line {}", c, line),
+            ParseBoardError::WrongNumber { x, y } => write!(f, "number at ({}, {}) does not match its mines", x, y),
+        }
+    }
+}
+
+impl Error for ParseBoardError {}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GenerationPolicy {
+    /// Any placement of mines is fine
+    #[default]
+    Random,
+    /// Only grids solvable by pure logic from the first click are accepted.
+    /// After `attempts` rejected grids the last one is kept anyway
+    NoGuess { attempts: usize },
+}
+
+/// Which cells are guaranteed to be free of mines on the first click
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FirstClickPolicy {
+    /// Only the clicked cell
+    #[default]
+    SafeCell,
+    /// The clicked cell and its neighbours, so the first click always opens an area
+    SafeArea,
+    /// The first click may hit a mine
+    Unprotected,
+}
+
+/// Named board configuration, used to group games of the same kind
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Difficulty {
+    /// 9x9 with 10 mines
+    #[default]
+    Beginner,
+    /// 16x16 with 40 mines
+    Intermediate,
+    /// 30x16 with 99 mines
+    Expert,
+    Custom { width: usize, height: usize, mines: usize },
+}
+
+impl Difficulty {
+    pub const PRESETS: [Difficulty; 3] = [Difficulty::Beginner, Difficulty::Intermediate, Difficulty::Expert];
+
+    /// Width, height and number of mines
+    pub fn size(&self) -> (usize, usize, usize) {
+        match *self {
+            Difficulty::Beginner => (9, 9, 10),
+            Difficulty::Intermediate => (16, 16, 40),
+            Difficulty::Expert => (30, 16, 99),
+            Difficulty::Custom { width, height, mines } => (width, height, mines),
+        }
+    }
+
+    /// The preset of this size if there is one, so equal boards are grouped together
+    pub fn from_size(width: usize, height: usize, mines: usize) -> Self {
+        Difficulty::PRESETS
+            .into_iter()
+            .find(|preset| preset.size() == (width, height, mines))
+            .unwrap_or(Difficulty::Custom { width, height, mines })
+    }
+
+    pub fn builder(&self) -> MinesweeperBuilder {
+        let (width, height, mines) = self.size();
+        MinesweeperBuilder::new(width, height, mines)
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difficulty::Beginner => write!(f, "Beginner"),
+            Difficulty::Intermediate => write!(f, "Intermediate"),
+            Difficulty::Expert => write!(f, "Expert"),
+            Difficulty::Custom { width, height, mines } => write!(f, "Custom {}x{}, {} mines", width, height, mines),
+        }
+    }
+}
+
+/// Validated way to create a [`Minesweeper`]
+///
+/// ```
+/// use minesweeper::minesweeper::MinesweeperBuilder;
+///
+/// let ms = MinesweeperBuilder::new(9, 9, 10).seed(42).build().unwrap();
+/// assert_eq!(ms.seed, 42);
+/// assert!(MinesweeperBuilder::new(3, 3, 9).build().is_err());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MinesweeperBuilder {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) number_of_mines: usize,
+    pub(crate) seed: Option<u64>,
+    pub(crate) generation: GenerationPolicy,
+    pub(crate) first_click: FirstClickPolicy,
+    practice: bool,
+}
+
+impl MinesweeperBuilder {
+    pub fn new(width: usize, height: usize, number_of_mines: usize) -> Self {
+        MinesweeperBuilder {
+            width,
+            height,
+            number_of_mines,
+            seed: None,
+            generation: GenerationPolicy::Random,
+            first_click: FirstClickPolicy::SafeCell,
+            practice: false,
+        }
+    }
+
+    /// Generate the grid from a fixed seed instead of a random one
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn generation(mut self, generation: GenerationPolicy) -> Self {
+        self.generation = generation;
+        self
+    }
+
+    pub fn first_click(mut self, first_click: FirstClickPolicy) -> Self {
+        self.first_click = first_click;
+        self
+    }
+
+    /// Allow undoing the click that hit a mine instead of ending the game
+    pub fn practice(mut self, practice: bool) -> Self {
+        self.practice = practice;
+        self
+    }
+
+    /// Draw the seed from the given generator
+    pub fn rng<R: Rng + ?Sized>(self, rng: &mut R) -> Self {
+        self.seed(rng.gen())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(ConfigError::EmptyGrid { width: self.width, height: self.height });
+        }
+
+        let protected = match self.first_click {
+            FirstClickPolicy::SafeCell => 1,
+            FirstClickPolicy::SafeArea => min(3, self.width) * min(3, self.height),
+            FirstClickPolicy::Unprotected => 0,
+        };
+        let max = self.width * self.height - protected;
+        if self.number_of_mines > max {
+            return Err(ConfigError::TooManyMines { mines: self.number_of_mines, max });
+        }
+
+        if self.generation == (GenerationPolicy::NoGuess { attempts: 0 }) {
+            return Err(ConfigError::NoAttempts);
+        }
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<Minesweeper, ConfigError> {
+        self.validate()?;
+
+        Ok(Minesweeper {
+            playing: true,
+            won: true,
+            grid: Grid::new(self.width, self.height, Cell::default()),
+            width: self.width,
+            height: self.height,
+            first_move: true,
+            seed: self.seed.unwrap_or_else(rand::random),
+            generation: self.generation,
+            first_click: self.first_click,
+            practice: self.practice,
+            number_of_mines: self.number_of_mines,
+            number_of_revealed_cells: 0,
+            number_of_flagged_mines: 0,
+            number_of_flags: 0,
+            undo_steps: vec![],
+            redo_actions: vec![],
+        })
+    }
+
+    /// A game on the given mines instead of a grid generated by the first open,
+    /// so the first click is not protected. Repeated cells count as one mine
+    pub fn build_with_mines(mut self, mines: &[Coord]) -> Result<Minesweeper, ConfigError> {
+        let mut mines = mines.to_vec();
+        mines.sort_unstable();
+        mines.dedup();
+        if let Some(&Coord { x, y }) = mines.iter().find(|c| c.x >= self.width || c.y >= self.height) {
+            return Err(ConfigError::MineOutOfBounds { x, y });
+        }
+
+        self.number_of_mines = mines.len();
+        self.first_click = FirstClickPolicy::Unprotected;
+        let mut ms = self.build()?;
+        ms.place_mines(&mines);
+        ms.first_move = false;
+        Ok(ms)
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Minesweeper {
     pub playing: bool,
     pub won: bool,
-    pub grid: Vec<Vec<Cell>>,
+    grid: Grid<Cell>,
     pub width: usize,
     pub height: usize,
+    /// The same seed and first click always produce the same grid, in every
+    /// version of the game
+    pub seed: u64,
+    // the policies are fixed by the builder, the grid and its counters depend on them
+    generation: GenerationPolicy,
+    first_click: FirstClickPolicy,
+    practice: bool,
     first_move: bool,
     number_of_mines: usize,
     number_of_revealed_cells: usize,
     number_of_flagged_mines: usize,
+    number_of_flags: usize,
+    undo_steps: Vec<Step>,
+    redo_actions: Vec<Action>,
 }
 
 impl Default for Minesweeper {
@@ -65,7 +399,7 @@ impl fmt::Display for Minesweeper {
         for y in 0..self.height {
             result.push_str(&((y + 1).to_string() + &" ".repeat(h_length - (y + 1).to_string().len() + 1) + "|"));
             for x in 0..self.width {
-                result.push_str(&format!("{}", self.grid[y][x]));
+                result.push_str(&format!("{}", self.grid[Coord::new(x, y)]));
                 result.push_str(&" ".repeat(w_length));
             }
             result.push('\n');
@@ -74,120 +408,494 @@ impl fmt::Display for Minesweeper {
     }
 }
 
+/// Reads a position written one row per line:
+///
+/// - `.` hidden cell, `*` hidden mine
+/// - `F` flagged mine, `f` flag on a safe cell
+/// - `0`-`8` revealed number, `X` revealed mine, which means the game is lost
+///
+/// Spaces between cells and blank lines are skipped.
+///
+/// ```
+/// use minesweeper::{grid::Coord, minesweeper::Minesweeper};
+///
+/// let ms: Minesweeper = "
+///     1 F .
+///     1 1 .
+///     . . .
+/// ".parse().unwrap();
+/// assert_eq!(ms.number_of_mines(), 1);
+/// assert!(ms.grid()[Coord::new(1, 0)].flag);
+/// ```
+impl FromStr for Minesweeper {
+    type Err = ParseBoardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut rows: Vec<Vec<char>> = vec![];
+        for (i, line) in s.lines().enumerate() {
+            let row: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+            if row.is_empty() {
+                continue;
+            }
+            if let Some(&c) = row.iter().find(|c| !matches!(c, '.' | '*' | 'F' | 'f' | 'X' | '0'..='8')) {
+                return Err(ParseBoardError::InvalidChar { line: i + 1, c });
+            }
+            if rows.first().map_or(false, |first| first.len() != row.len()) {
+                return Err(ParseBoardError::UnevenRow { line: i + 1 });
+            }
+            rows.push(row);
+        }
+        if rows.is_empty() {
+            return Err(ParseBoardError::Empty);
+        }
+
+        let mut mines = vec![];
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &c) in row.iter().enumerate() {
+                if matches!(c, '*' | 'F' | 'X') {
+                    mines.push(Coord::new(x, y));
+                }
+            }
+        }
+        let mut ms = Minesweeper::from_mine_layout(rows[0].len(), rows.len(), &mines)
+            .expect("every mine is inside the grid");
+
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &c) in row.iter().enumerate() {
+                let coord = Coord::new(x, y);
+                match c {
+                    'F' | 'f' => ms.toggle_flag(coord),
+                    'X' => {
+                        ms.grid[coord].revealed = true;
+                        ms.playing = false;
+                        ms.won = false;
+                    }
+                    '0'..='8' => {
+                        if ms.grid[coord].surrounds != c as u8 - b'0' {
+                            return Err(ParseBoardError::WrongNumber { x, y });
+                        }
+                        ms.grid[coord].revealed = true;
+                        ms.number_of_revealed_cells += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if ms.playing {
+            ms.check_for_win();
+        }
+        Ok(ms)
+    }
+}
+
 impl Minesweeper {
+    /// Panics if the configuration is invalid, use [`MinesweeperBuilder`] to handle it
     pub fn new(width: usize, height: usize, number_of_mines: usize) -> Self {
-        let grid = vec![vec![Cell::default(); width]; height];
-        Minesweeper {
-            playing: true,
-            won: true,
-            grid,
-            width,
-            height,
-            first_move: true,
-            number_of_mines,
-            number_of_revealed_cells: 0,
-            number_of_flagged_mines: 0,
+        match MinesweeperBuilder::new(width, height, number_of_mines).build() {
+            Ok(ms) => ms,
+            Err(e) => panic!("invalid Minesweeper configuration: {}", e),
+        }
+    }
+
+    /// A game on a grid with mines at the given cells, the first click is not protected.
+    /// Repeated cells count as one mine
+    pub fn from_mine_layout(width: usize, height: usize, mines: &[Coord]) -> Result<Self, ConfigError> {
+        MinesweeperBuilder::new(width, height, mines.len()).seed(0).build_with_mines(mines)
+    }
+
+    /// The mines are on the grid: it was generated by the first open or given to
+    /// [`from_mine_layout`](Self::from_mine_layout). Undoing the first open takes them away
+    pub fn mines_placed(&self) -> bool {
+        !self.first_move
+    }
+
+    // every position kept to undo an action is on the grid, a tampered save
+    // may break it and make `undo` panic
+    #[cfg(feature = "serde")]
+    pub(crate) fn history_is_on_grid(&self) -> bool {
+        self.undo_steps.iter().all(|step| {
+            self.grid.contains(step.action.position().into()) && step.revealed.iter().all(|&c| self.grid.contains(c))
+        })
+    }
+
+    // the counters agree with the cells, a tampered save may break them and
+    // make the next action overflow
+    #[cfg(feature = "serde")]
+    pub(crate) fn counters_match_grid(&self) -> bool {
+        let count = |f: fn(&Cell) -> bool| self.grid.iter().filter(|cell| f(cell)).count();
+        let mines = match self.first_move {
+            // the grid is generated later, it must be possible
+            true => {
+                count(|cell| cell.mine) == 0
+                    && MinesweeperBuilder::new(self.width, self.height, self.number_of_mines)
+                        .generation(self.generation)
+                        .first_click(self.first_click)
+                        .validate()
+                        .is_ok()
+            }
+            false => count(|cell| cell.mine) == self.number_of_mines,
+        };
+        mines
+            && count(|cell| cell.flag) == self.number_of_flags
+            && count(|cell| cell.revealed && !cell.mine) == self.number_of_revealed_cells
+            && count(|cell| cell.flag && cell.mine) == self.number_of_flagged_mines
+    }
+
+    /// The cells of the game, read only so the counters stay right
+    pub fn grid(&self) -> &Grid<Cell> {
+        &self.grid
+    }
+
+    pub fn number_of_mines(&self) -> usize {
+        self.number_of_mines
+    }
+
+    pub fn generation(&self) -> GenerationPolicy {
+        self.generation
+    }
+
+    pub fn first_click(&self) -> FirstClickPolicy {
+        self.first_click
+    }
+
+    /// A lost game can be undone
+    pub fn practice(&self) -> bool {
+        self.practice
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        Difficulty::from_size(self.width, self.height, self.number_of_mines)
+    }
+
+    pub fn number_of_flags(&self) -> usize {
+        self.number_of_flags
+    }
+
+    /// Revealed cells without a mine
+    pub fn number_of_revealed_cells(&self) -> usize {
+        self.number_of_revealed_cells
+    }
+
+    /// Cells holding a mine, empty until the grid is generated by the first move
+    pub fn mine_layout(&self) -> Vec<Coord> {
+        self.grid.cells().filter(|(_, cell)| cell.mine).map(|(c, _)| c).collect()
+    }
+
+    pub fn open(&mut self, x: usize, y: usize) -> Result<Outcome, MinesweeperError> {
+        self.act(Action::Open(x, y))
+    }
+
+    pub fn flag(&mut self, x: usize, y: usize) -> Result<Outcome, MinesweeperError> {
+        self.act(Action::Flag(x, y))
+    }
+
+    /// Opens every unflagged neighbour of a revealed number once the number of
+    /// flags around it matches, a wrongly placed flag loses the game
+    pub fn chord(&mut self, x: usize, y: usize) -> Result<Outcome, MinesweeperError> {
+        self.act(Action::Chord(x, y))
+    }
+
+    /// Takes back the last action that changed the grid, returns `None` if there is nothing to undo.
+    ///
+    /// A finished game can't be undone, except for a lost game in practice mode.
+    pub fn undo(&mut self) -> Result<Option<Action>, MinesweeperError> {
+        if !self.playing && (self.won || !self.practice) {
+            return Err(MinesweeperError::GameOver);
+        }
+
+        let Some(step) = self.undo_steps.pop() else {
+            return Ok(None);
+        };
+
+        for &c in &step.revealed {
+            self.grid[c].revealed = false;
+            if !self.grid[c].mine {
+                self.number_of_revealed_cells -= 1;
+            }
+        }
+
+        if let Action::Flag(x, y) = step.action {
+            self.toggle_flag(Coord::new(x, y));
+        }
+
+        // the grid is generated again by redoing the first move, the seed makes it the same
+        if step.generated {
+            for cell in self.grid.iter_mut() {
+                cell.mine = false;
+                cell.surrounds = 0;
+            }
+            self.number_of_flagged_mines = 0;
+            self.first_move = true;
         }
+
+        self.playing = step.playing;
+        self.won = step.won;
+        self.redo_actions.push(step.action);
+        Ok(Some(step.action))
     }
 
-    pub fn open(&mut self, x: usize, y: usize) {
+    /// Repeats the last undone action, returns `None` if there is nothing to redo
+    pub fn redo(&mut self) -> Result<Option<Action>, MinesweeperError> {
+        let Some(action) = self.redo_actions.pop() else {
+            return Ok(None);
+        };
+
+        match self.apply(action) {
+            Ok(_) => Ok(Some(action)),
+            Err(e) => {
+                self.redo_actions.push(action);
+                Err(e)
+            }
+        }
+    }
+
+    // a new action from the player makes the undone ones impossible to redo
+    fn act(&mut self, action: Action) -> Result<Outcome, MinesweeperError> {
+        let outcome = self.apply(action)?;
+        if outcome != Outcome::Ignored {
+            self.redo_actions.clear();
+        }
+        Ok(outcome)
+    }
+
+    fn apply(&mut self, action: Action) -> Result<Outcome, MinesweeperError> {
+        let (x, y) = action.position();
+        self.check_action(x, y)?;
+
+        let mut step = Step {
+            action,
+            revealed: vec![],
+            generated: self.first_move,
+            playing: self.playing,
+            won: self.won,
+        };
+
+        let c = Coord::new(x, y);
+        let outcome = match action {
+            Action::Open(..) => self.open_cell(c, &mut step.revealed),
+            Action::Flag(..) => self.flag_cell(c),
+            Action::Chord(..) => self.chord_cell(c, &mut step.revealed),
+        };
+
+        if outcome != Outcome::Ignored {
+            step.generated &= !self.first_move;
+            self.undo_steps.push(step);
+        }
+        Ok(outcome)
+    }
+
+    fn open_cell(&mut self, c: Coord, revealed: &mut Vec<Coord>) -> Outcome {
+        if self.grid[c].flag || self.grid[c].revealed {
+            return Outcome::Ignored;
+        }
+
         // generate a grid "after" the first move to prevent from failing
         if self.first_move {
-            self.generate_grid(x, y);
+            self.generate_grid(c);
             self.first_move = false;
         }
 
-        if !self.grid[y][x].flag {
-            match self.grid[y][x].mine {
-                true => {
-                    self.grid[y][x].revealed = true;
-                    self.playing = false;
-                    self.won = false;
-                    return;
-                }
-                false => {
-                    self.open_empty(x, y);
-                }
-            }
-           self.check_for_win();        
+        if self.grid[c].mine {
+            self.grid[c].revealed = true;
+            revealed.push(c);
+            self.playing = false;
+            self.won = false;
+            return Outcome::HitMine;
         }
+
+        self.open_empty(c, revealed);
+        self.check_for_win();
+        Outcome::Revealed(revealed.len())
     }
 
-    pub fn flag(&mut self, x: usize, y: usize) {
-        if self.grid[y][x].revealed {
-            return;
+    fn flag_cell(&mut self, c: Coord) -> Outcome {
+        if self.grid[c].revealed {
+            return Outcome::Ignored;
         }
 
-        self.grid[y][x].flag = !self.grid[y][x].flag;
+        self.toggle_flag(c);
+        self.check_for_win();
 
-        if self.grid[y][x].flag && self.grid[y][x].mine {
+        match self.grid[c].flag {
+            true => Outcome::Flagged,
+            false => Outcome::Unflagged,
+        }
+    }
+
+    fn toggle_flag(&mut self, c: Coord) {
+        let cell = &mut self.grid[c];
+        cell.flag = !cell.flag;
+
+        match cell.flag {
+            true => self.number_of_flags += 1,
+            false => self.number_of_flags -= 1,
+        }
+
+        if cell.flag && cell.mine {
             self.number_of_flagged_mines += 1;
-        } else if !self.grid[y][x].flag && self.grid[y][x].mine {
+        } else if !cell.flag && cell.mine {
             self.number_of_flagged_mines -= 1;
         }
+    }
+
+    fn chord_cell(&mut self, c: Coord, revealed: &mut Vec<Coord>) -> Outcome {
+        let cell = self.grid[c];
+        if !cell.revealed || cell.mine || cell.surrounds == 0 {
+            return Outcome::Ignored;
+        }
+
+        let flags = self.grid.neighbours(c).filter(|&n| self.grid[n].flag).count();
+        if flags != cell.surrounds as usize {
+            return Outcome::Ignored;
+        }
 
-        self.check_for_win();        
+        let mut hit_mine = false;
+        for n in self.grid.neighbours(c) {
+            let neighbour = self.grid[n];
+            if neighbour.flag || neighbour.revealed {
+                continue;
+            }
+            if neighbour.mine {
+                self.grid[n].revealed = true;
+                revealed.push(n);
+                hit_mine = true;
+            } else {
+                self.open_empty(n, revealed);
+            }
+        }
+
+        if hit_mine {
+            self.playing = false;
+            self.won = false;
+            return Outcome::HitMine;
+        }
+
+        self.check_for_win();
+        match revealed.len() {
+            0 => Outcome::Ignored,
+            n => Outcome::Revealed(n),
+        }
     }
 
-    fn open_empty(&mut self, x: usize, y: usize) {
-        if self.grid[y][x].revealed {
-            return;
+    fn check_action(&self, x: usize, y: usize) -> Result<(), MinesweeperError> {
+        if x >= self.width || y >= self.height {
+            return Err(MinesweeperError::OutOfBounds { x, y });
+        }
+        if !self.playing {
+            return Err(MinesweeperError::GameOver);
         }
+        Ok(())
+    }
 
-        if self.grid[y][x].surrounds != 0 {
-            self.number_of_revealed_cells += 1;
-            self.grid[y][x].revealed = true;
+    // pushes every newly revealed cell to `revealed`, a cell is revealed when
+    // it is queued so it is never queued twice and the stack depth stays the
+    // same on any grid
+    fn open_empty(&mut self, c: Coord, revealed: &mut Vec<Coord>) {
+        if self.grid[c].revealed {
             return;
         }
 
-        if !self.grid[y][x].revealed {
-            self.number_of_revealed_cells += 1;
-        }
-        self.grid[y][x].revealed = true;
+        let mut queue = VecDeque::new();
+        self.reveal(c, revealed);
+        queue.push_back(c);
 
-        let xrange = max(0, x as isize - 1) as usize..=min(self.width - 1, x + 1);
-        let yrange = max(0, y as isize - 1) as usize..=min(self.height - 1, y + 1);
+        while let Some(c) = queue.pop_front() {
+            if self.grid[c].surrounds != 0 {
+                continue;
+            }
 
-        for cy in yrange {
-            for cx in xrange.clone() {
-                self.open_empty(cx, cy);
+            for n in self.grid.neighbours(c) {
+                if !self.grid[n].revealed {
+                    self.reveal(n, revealed);
+                    queue.push_back(n);
+                }
             }
         }
     }
 
-    fn generate_grid(&mut self, x: usize, y: usize) {
-        if self.number_of_mines == self.width * self.height {
-            for y in 0..self.height  {
-                for x in 0..self.width {
-                    self.grid[y][x].mine = true;
-                }
+    fn reveal(&mut self, c: Coord, revealed: &mut Vec<Coord>) {
+        self.number_of_revealed_cells += 1;
+        self.grid[c].revealed = true;
+        revealed.push(c);
+    }
+
+    fn generate_grid(&mut self, first: Coord) {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let candidates: Vec<Coord> = self.grid
+            .coords()
+            .filter(|c| match self.first_click {
+                FirstClickPolicy::SafeCell => *c != first,
+                FirstClickPolicy::SafeArea => c.y.abs_diff(first.y) > 1 || c.x.abs_diff(first.x) > 1,
+                FirstClickPolicy::Unprotected => true,
+            })
+            .collect();
+
+        let attempts = match self.generation {
+            GenerationPolicy::Random => 1,
+            GenerationPolicy::NoGuess { attempts } => attempts,
+        };
+
+        for _ in 0..attempts {
+            let mines: Vec<Coord> = candidates
+                .choose_multiple(&mut rng, self.number_of_mines)
+                .copied()
+                .collect();
+            self.place_mines(&mines);
+
+            if self.generation == GenerationPolicy::Random || self.is_solvable_from(first) {
+                break;
             }
-            return;
         }
+    }
 
-        let mut rng = rand::thread_rng();
-        let mut mines: Vec<(usize, usize)> = vec![];
-        for cy in 0..self.height {
-            for cx in 0..self.width {
-                if !(cy == y && cx == x) {
-                    mines.push((cx, cy));
-                }
+    fn place_mines(&mut self, mines: &[Coord]) {
+        for cell in self.grid.iter_mut() {
+            cell.mine = false;
+            cell.surrounds = 0;
+        }
+        self.number_of_flagged_mines = 0;
+
+        for &c in mines {
+            self.grid[c].mine = true;
+            // flags placed before the first move may land on a mine
+            if self.grid[c].flag {
+                self.number_of_flagged_mines += 1;
+            }
+
+            for n in self.grid.neighbours(c) {
+                self.grid[n].surrounds += 1;
             }
         }
+    }
 
-        for (cx, cy) in mines.choose_multiple(&mut rng, self.number_of_mines) {
-            let (x, y) = (*cx, *cy);
-            self.grid[y][x].mine = true;
+    // plays a copy of the grid from `first` opening only the cells the solver proves safe
+    fn is_solvable_from(&self, first: Coord) -> bool {
+        let mut game = self.clone();
+        game.first_move = false;
+        // the solver sees flags as hidden cells and would call them safe,
+        // while opening a flagged cell does nothing
+        for cell in game.grid.iter_mut() {
+            cell.flag = false;
+        }
+        game.number_of_flags = 0;
+        game.number_of_flagged_mines = 0;
 
-            let xrange = max(0, x as isize - 1) as usize..=min(self.width - 1, x + 1);
-            let yrange = max(0, y as isize - 1) as usize..=min(self.height - 1, y + 1);
-            
-            for cy in yrange {
-                for cx in xrange.clone() {
-                    if !(cy == y && cx == x) {
-                        self.grid[cy][cx].surrounds += 1;
-                    }
-                }
+        if !matches!(game.open(first.x, first.y), Ok(Outcome::Revealed(_))) {
+            return false;
+        }
+
+        loop {
+            if game.number_of_revealed_cells == self.width * self.height - self.number_of_mines {
+                return true;
+            }
+
+            let safe = solver::solve(&game).safe;
+            if safe.is_empty() {
+                return false;
+            }
+            for c in safe {
+                let _ = game.open(c.x, c.y);
             }
         }
     }
@@ -197,4 +905,187 @@ impl Minesweeper {
             self.playing = false;
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_every_kind_of_cell() {
+        let ms: Minesweeper = "
+            . * F
+            f 2 2
+            0 0 .
+        ".parse().unwrap();
+
+        assert_eq!((ms.width, ms.height), (3, 3));
+        assert_eq!(ms.number_of_mines(), 2);
+        assert_eq!(ms.mine_layout(), vec![Coord::new(1, 0), Coord::new(2, 0)]);
+        assert!(ms.grid()[Coord::new(2, 0)].flag && ms.grid()[Coord::new(0, 1)].flag);
+        assert!(!ms.grid()[Coord::new(0, 0)].revealed && !ms.grid()[Coord::new(1, 0)].revealed);
+        assert!(ms.grid()[Coord::new(1, 1)].revealed);
+        assert_eq!(ms.grid()[Coord::new(1, 1)].surrounds, 2);
+        assert!(ms.playing);
+    }
+
+    #[test]
+    fn revealed_mine_means_lost() {
+        let ms: Minesweeper = "X 1
+1 1".parse().unwrap();
+        assert!(!ms.playing);
+        assert!(!ms.won);
+    }
+
+    #[test]
+    fn rejects_malformed_boards() {
+        assert_eq!("".parse::<Minesweeper>().err(), Some(ParseBoardError::Empty));
+        assert_eq!("..\n.".parse::<Minesweeper>().err(), Some(ParseBoardError::UnevenRow { line: 2 }));
+        assert_eq!(".?".parse::<Minesweeper>().err(), Some(ParseBoardError::InvalidChar { line: 1, c: '?' }));
+        assert_eq!("*2".parse::<Minesweeper>().err(), Some(ParseBoardError::WrongNumber { x: 1, y: 0 }));
+    }
+
+    #[test]
+    fn opening_a_zero_floods_the_area() {
+        let mut ms: Minesweeper = "
+            . . . .
+            . . . .
+            . . . .
+            . . . *
+        ".parse().unwrap();
+
+        assert_eq!(ms.open(0, 0), Ok(Outcome::Revealed(15)));
+        assert!(!ms.grid()[Coord::new(3, 3)].revealed);
+        // the game is won once the mines are flagged too
+        assert!(ms.playing);
+        assert_eq!(ms.flag(3, 3), Ok(Outcome::Flagged));
+        assert!(!ms.playing);
+        assert!(ms.won);
+    }
+
+    #[test]
+    fn chord_opens_unflagged_neighbours() {
+        let mut ms: Minesweeper = "
+            F 1 .
+            1 1 .
+            . . .
+        ".parse().unwrap();
+
+        assert_eq!(ms.chord(1, 1), Ok(Outcome::Revealed(5)));
+        assert!(!ms.playing);
+        assert!(ms.won);
+    }
+
+    #[test]
+    fn chord_with_a_wrong_flag_hits_the_mine() {
+        let mut ms: Minesweeper = "
+            * 1 .
+            f 1 .
+            . . .
+        ".parse().unwrap();
+
+        assert_eq!(ms.chord(1, 1), Ok(Outcome::HitMine));
+        assert!(!ms.won);
+    }
+
+    #[test]
+    fn undo_hides_the_opened_cells_again() {
+        let mut ms: Minesweeper = "
+            . . .
+            . 1 1
+            . 1 *
+        ".parse().unwrap();
+
+        ms.open(0, 0).unwrap();
+        assert_eq!(ms.undo(), Ok(Some(Action::Open(0, 0))));
+        assert!(!ms.grid()[Coord::new(0, 0)].revealed);
+        assert!(ms.grid()[Coord::new(1, 1)].revealed);
+        assert_eq!(ms.redo(), Ok(Some(Action::Open(0, 0))));
+        assert!(ms.grid()[Coord::new(0, 0)].revealed);
+    }
+
+    #[test]
+    fn builds_a_practice_game_on_given_mines() {
+        let mut ms = MinesweeperBuilder::new(3, 3, 0)
+            .first_click(FirstClickPolicy::SafeArea)
+            .practice(true)
+            .build_with_mines(&[Coord::new(0, 0), Coord::new(0, 0), Coord::new(2, 2)])
+            .unwrap();
+        assert_eq!(ms.number_of_mines(), 2);
+        assert_eq!(ms.first_click(), FirstClickPolicy::Unprotected);
+
+        ms.open(0, 0).unwrap();
+        assert!(!ms.playing);
+        assert_eq!(ms.undo().unwrap(), Some(Action::Open(0, 0)));
+        assert_eq!(ms.mine_layout(), [Coord::new(0, 0), Coord::new(2, 2)]);
+    }
+
+    #[test]
+    fn seeded_grids_stay_the_same() {
+        // saved games and replays draw their grid from the seed again, another generator would break them
+        let mut ms = MinesweeperBuilder::new(5, 5, 4).seed(42).build().unwrap();
+        ms.open(2, 2).unwrap();
+        assert_eq!(ms.mine_layout(), [Coord::new(3, 0), Coord::new(0, 3), Coord::new(3, 3), Coord::new(3, 4)]);
+    }
+
+    #[test]
+    fn no_guess_generation_ignores_early_flags() {
+        let mut ms = MinesweeperBuilder::new(9, 9, 10)
+            .seed(10)
+            .generation(GenerationPolicy::NoGuess { attempts: 1 })
+            .build()
+            .unwrap();
+
+        assert_eq!(ms.flag(0, 0), Ok(Outcome::Flagged));
+        assert!(matches!(ms.open(4, 4), Ok(Outcome::Revealed(_))));
+        assert!(ms.grid()[Coord::new(0, 0)].flag);
+        assert_eq!(ms.number_of_flags(), 1);
+    }
+
+    #[test]
+    fn custom_sizes_of_presets_are_presets() {
+        assert_eq!(Difficulty::from_size(30, 16, 99), Difficulty::Expert);
+        assert_eq!(Difficulty::from_size(16, 30, 99), Difficulty::Custom { width: 16, height: 30, mines: 99 });
+        for preset in Difficulty::PRESETS {
+            let ms = preset.builder().build().unwrap();
+            assert_eq!(ms.difficulty(), preset);
+        }
+    }
+
+    #[test]
+    fn opens_a_huge_empty_board() {
+        // deep enough to overflow the stack of a recursive flood fill
+        let (width, height) = (2000, 2000);
+        let mut ms = Minesweeper::from_mine_layout(width, height, &[Coord::new(width - 1, height - 1)]).unwrap();
+
+        assert_eq!(ms.open(0, 0), Ok(Outcome::Revealed(width * height - 1)));
+        assert_eq!(ms.number_of_revealed_cells(), width * height - 1);
+
+        // undo hides the whole area again
+        ms.undo().unwrap();
+        assert_eq!(ms.number_of_revealed_cells(), 0);
+        ms.redo().unwrap();
+        assert_eq!(ms.flag(width - 1, height - 1), Ok(Outcome::Flagged));
+        assert!(ms.won);
+    }
+
+    #[test]
+    fn reveals_every_cell_of_a_sparse_board_once() {
+        let (width, height) = (2000, 2000);
+        let mut ms = MinesweeperBuilder::new(width, height, 2000).seed(3).build().unwrap();
+
+        let Ok(Outcome::Revealed(count)) = ms.open(width / 2, height / 2) else {
+            panic!("the first click is safe");
+        };
+        assert_eq!(count, ms.number_of_revealed_cells());
+        // a low density leaves most of the grid in a single opening
+        assert!(count > width * height / 2);
+
+        // a revealed zero never borders a hidden cell
+        let grid = ms.grid();
+        for (c, cell) in grid.cells() {
+            if cell.revealed && cell.surrounds == 0 {
+                assert!(grid.neighbours(c).all(|n| grid[n].revealed));
+            }
+        }
+    }
+}
@@ -0,0 +1,401 @@
+use crate::{
+    grid::{Coord, Grid},
+    minesweeper::Minesweeper,
+};
+
+// a revealed number over the frontier: `mines` of `cells` hold a mine
+struct Constraint {
+    cells: Vec<usize>,
+    mines: usize,
+}
+
+// every way to place mines in one connected part of the frontier
+struct Component {
+    cells: Vec<usize>,
+    // number of placements with `k` mines
+    solutions: Vec<f64>,
+    // number of placements with `k` mines that put a mine into each cell
+    mine_solutions: Vec<Vec<f64>>,
+}
+
+/// Exact chance of a mine under every hidden cell, `None` for revealed cells.
+///
+/// All placements of mines around the revealed numbers (the frontier) are
+/// enumerated, each weighted by the number of ways to put the rest of the
+/// mines into the hidden cells that touch no number. Flags are treated as
+/// hidden cells. A board that contradicts itself gives `None` everywhere.
+///
+/// The enumeration is exponential in the size of the largest connected part
+/// of the frontier, which is fine for the usual boards but not for huge ones.
+pub fn mine_probabilities(ms: &Minesweeper) -> Grid<Option<f64>> {
+    let grid = ms.grid();
+    let mut probabilities = Grid::new(ms.width, ms.height, None);
+
+    // the index of every hidden cell next to a revealed number
+    let mut frontier: Grid<Option<usize>> = Grid::new(ms.width, ms.height, None);
+    let mut frontier_cells: Vec<Coord> = vec![];
+    let mut interior_cells: Vec<Coord> = vec![];
+    let mut constraints = vec![];
+    let mut revealed_mines = 0;
+
+    for (c, cell) in grid.cells() {
+        if cell.revealed {
+            // only possible after the game was lost
+            if cell.mine {
+                revealed_mines += 1;
+            }
+            continue;
+        }
+
+        let touches_number = grid.neighbours(c).any(|n| grid[n].revealed && !grid[n].mine);
+        if touches_number {
+            frontier[c] = Some(frontier_cells.len());
+            frontier_cells.push(c);
+        } else {
+            interior_cells.push(c);
+        }
+    }
+
+    for (c, cell) in grid.cells() {
+        if !cell.revealed || cell.mine {
+            continue;
+        }
+
+        let mut cells = vec![];
+        let mut known_mines = 0;
+        for n in grid.neighbours(c) {
+            if let Some(i) = frontier[n] {
+                cells.push(i);
+            } else if grid[n].revealed && grid[n].mine {
+                known_mines += 1;
+            }
+        }
+
+        if known_mines > cell.surrounds as usize {
+            return probabilities;
+        }
+        if !cells.is_empty() {
+            constraints.push(Constraint {
+                cells,
+                mines: cell.surrounds as usize - known_mines,
+            });
+        }
+    }
+
+    let Some(remaining) = ms.number_of_mines().checked_sub(revealed_mines) else {
+        return probabilities;
+    };
+
+    let components: Vec<Component> = components(frontier_cells.len(), &constraints)
+        .into_iter()
+        .map(|cells| enumerate(cells, &constraints, frontier_cells.len()))
+        .collect();
+
+    // weight of a total number of frontier mines: ways to place the rest in the interior
+    let interior = interior_cells.len();
+    let log_factorials = log_factorials(interior);
+    let log_weight = |frontier_mines: usize| -> Option<f64> {
+        let rest = remaining.checked_sub(frontier_mines)?;
+        if rest > interior {
+            return None;
+        }
+        Some(log_factorials[interior] - log_factorials[rest] - log_factorials[interior - rest])
+    };
+
+    let all = combine(components.iter());
+    let max_log_weight = (0..all.len())
+        .filter(|&k| all[k] > 0.)
+        .filter_map(&log_weight)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let weight = |k: usize| -> f64 {
+        match log_weight(k) {
+            Some(w) => (w - max_log_weight).exp(),
+            None => 0.,
+        }
+    };
+
+    let total: f64 = (0..all.len()).map(|k| all[k] * weight(k)).sum();
+    if total == 0. || !total.is_finite() {
+        return probabilities;
+    }
+
+    // each interior cell holds an equal share of the mines left for the interior
+    if interior > 0 {
+        let expected: f64 = (0..all.len())
+            .map(|k| all[k] * weight(k) * remaining.saturating_sub(k) as f64 / interior as f64)
+            .sum();
+        for &c in &interior_cells {
+            probabilities[c] = Some(expected / total);
+        }
+    }
+
+    for (i, component) in components.iter().enumerate() {
+        let others = combine(components.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, c)| c));
+
+        for (c, &cell) in component.cells.iter().enumerate() {
+            let mut mine_weight = 0.;
+            for (k, mine_solutions) in component.mine_solutions.iter().enumerate() {
+                for (j, &ways) in others.iter().enumerate() {
+                    mine_weight += mine_solutions[c] * ways * weight(k + j);
+                }
+            }
+            probabilities[frontier_cells[cell]] = Some(mine_weight / total);
+        }
+    }
+
+    probabilities
+}
+
+// groups frontier cells that are linked through shared numbers
+fn components(number_of_cells: usize, constraints: &[Constraint]) -> Vec<Vec<usize>> {
+    let mut parent: Vec<usize> = (0..number_of_cells).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    for constraint in constraints {
+        for &cell in &constraint.cells[1..] {
+            let (a, b) = (root(&mut parent, constraint.cells[0]), root(&mut parent, cell));
+            parent[a] = b;
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = vec![vec![]; number_of_cells];
+    for cell in 0..number_of_cells {
+        let r = root(&mut parent, cell);
+        groups[r].push(cell);
+    }
+    groups.into_iter().filter(|g| !g.is_empty()).collect()
+}
+
+fn enumerate(cells: Vec<usize>, constraints: &[Constraint], number_of_cells: usize) -> Component {
+    let mut position = vec![None; number_of_cells];
+    for (i, &cell) in cells.iter().enumerate() {
+        position[cell] = Some(i);
+    }
+
+    // constraints of this component, referring to positions in `cells`
+    let local: Vec<Constraint> = constraints
+        .iter()
+        .filter(|c| position[c.cells[0]].is_some())
+        .map(|c| Constraint {
+            cells: c.cells.iter().filter_map(|&cell| position[cell]).collect(),
+            mines: c.mines,
+        })
+        .collect();
+
+    let mut of_cell = vec![vec![]; cells.len()];
+    for (i, constraint) in local.iter().enumerate() {
+        for &cell in &constraint.cells {
+            of_cell[cell].push(i);
+        }
+    }
+
+    let mut search = Search {
+        constraints: &local,
+        of_cell: &of_cell,
+        placed: vec![0; local.len()],
+        open: local.iter().map(|c| c.cells.len()).collect(),
+        mine: vec![false; cells.len()],
+        solutions: vec![0.; cells.len() + 1],
+        mine_solutions: vec![vec![0.; cells.len()]; cells.len() + 1],
+    };
+    search.place(0, 0);
+
+    Component {
+        cells,
+        solutions: search.solutions,
+        mine_solutions: search.mine_solutions,
+    }
+}
+
+struct Search<'a> {
+    constraints: &'a [Constraint],
+    of_cell: &'a [Vec<usize>],
+    // mines placed and cells left undecided around each number
+    placed: Vec<usize>,
+    open: Vec<usize>,
+    mine: Vec<bool>,
+    solutions: Vec<f64>,
+    mine_solutions: Vec<Vec<f64>>,
+}
+
+impl Search<'_> {
+    fn place(&mut self, cell: usize, mines: usize) {
+        if cell == self.mine.len() {
+            self.solutions[mines] += 1.;
+            for (i, &mine) in self.mine.iter().enumerate() {
+                if mine {
+                    self.mine_solutions[mines][i] += 1.;
+                }
+            }
+            return;
+        }
+
+        for mine in [false, true] {
+            let fits = self.of_cell[cell].iter().all(|&c| {
+                let placed = self.placed[c] + mine as usize;
+                placed <= self.constraints[c].mines && placed + self.open[c] > self.constraints[c].mines
+            });
+            if !fits {
+                continue;
+            }
+
+            for &c in &self.of_cell[cell] {
+                self.placed[c] += mine as usize;
+                self.open[c] -= 1;
+            }
+            self.mine[cell] = mine;
+
+            self.place(cell + 1, mines + mine as usize);
+
+            for &c in &self.of_cell[cell] {
+                self.placed[c] -= mine as usize;
+                self.open[c] += 1;
+            }
+        }
+        self.mine[cell] = false;
+    }
+}
+
+// number of placements over all given components for each total of mines
+fn combine<'a>(components: impl Iterator<Item = &'a Component>) -> Vec<f64> {
+    let mut all = vec![1.];
+    for component in components {
+        let mut next = vec![0.; all.len() + component.solutions.len() - 1];
+        for (i, &a) in all.iter().enumerate() {
+            for (j, &b) in component.solutions.iter().enumerate() {
+                next[i + j] += a * b;
+            }
+        }
+        all = next;
+    }
+    all
+}
+
+fn log_factorials(n: usize) -> Vec<f64> {
+    let mut result = vec![0.; n + 1];
+    for i in 1..=n {
+        result[i] = result[i - 1] + (i as f64).ln();
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minesweeper::MinesweeperBuilder;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // every placement of the mines left over the hidden cells, counted one by one
+    fn brute_force(ms: &Minesweeper) -> Grid<Option<f64>> {
+        let grid = ms.grid();
+        let hidden: Vec<Coord> = grid.coords().filter(|&c| !grid[c].revealed).collect();
+        let revealed_mines = grid.iter().filter(|cell| cell.revealed && cell.mine).count();
+        let mut probabilities = Grid::new(ms.width, ms.height, None);
+        let Some(remaining) = ms.number_of_mines().checked_sub(revealed_mines) else {
+            return probabilities;
+        };
+
+        let mut total = 0;
+        let mut with_mine = vec![0; hidden.len()];
+        for placement in 0u32..1 << hidden.len() {
+            if placement.count_ones() as usize != remaining {
+                continue;
+            }
+            let mut mines = Grid::from_fn(ms.width, ms.height, |c| grid[c].revealed && grid[c].mine);
+            for (i, &c) in hidden.iter().enumerate() {
+                mines[c] = placement >> i & 1 == 1;
+            }
+            let fits = grid.cells().all(|(c, cell)| {
+                !cell.revealed || cell.mine || grid.neighbours(c).filter(|&n| mines[n]).count() == cell.surrounds as usize
+            });
+            if fits {
+                total += 1;
+                for (i, count) in with_mine.iter_mut().enumerate() {
+                    *count += (placement >> i & 1) as usize;
+                }
+            }
+        }
+
+        if total > 0 {
+            for (&c, &count) in hidden.iter().zip(&with_mine) {
+                probabilities[c] = Some(count as f64 / total as f64);
+            }
+        }
+        probabilities
+    }
+
+    fn assert_matches_brute_force(ms: &Minesweeper) {
+        let (exact, expected) = (mine_probabilities(ms), brute_force(ms));
+        for (c, &p) in expected.cells() {
+            match (exact[c], p) {
+                (Some(a), Some(b)) => assert!((a - b).abs() < 1e-9, "{}: {} instead of {}", c, a, b),
+                (a, b) => assert_eq!(a, b, "{}", c),
+            }
+        }
+    }
+
+    #[test]
+    fn weighs_the_frontier_by_the_interior() {
+        let ms: Minesweeper = "
+            1 . . .
+            * . . .
+            . . * .
+        ".parse().unwrap();
+
+        let probabilities = mine_probabilities(&ms);
+        assert_eq!(probabilities[Coord::new(0, 0)], None);
+        assert_matches_brute_force(&ms);
+    }
+
+    #[test]
+    fn matches_brute_force_on_played_boards() {
+        let mut rng = StdRng::seed_from_u64(8);
+        for seed in 0..150 {
+            let (width, height) = (rng.gen_range(3..=6), rng.gen_range(3..=4));
+            let mines = rng.gen_range(1..width * height / 2);
+            let mut ms = MinesweeperBuilder::new(width, height, mines).seed(seed).build().unwrap();
+
+            // some games are lost on the way and keep their revealed mine
+            while ms.playing && width * height - ms.number_of_revealed_cells() > 16 {
+                let _ = ms.open(rng.gen_range(0..width), rng.gen_range(0..height));
+            }
+            let _ = ms.open(rng.gen_range(0..width), rng.gen_range(0..height));
+            if width * height - ms.number_of_revealed_cells() <= 16 {
+                assert_matches_brute_force(&ms);
+            }
+        }
+    }
+
+    #[test]
+    fn lost_board_counts_the_revealed_mine() {
+        let ms: Minesweeper = "
+            1 . . .
+            X 2 . .
+            . . * .
+        ".parse().unwrap();
+        assert_matches_brute_force(&ms);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn contradiction_has_no_probabilities() {
+        use crate::save::Format;
+
+        let ms: Minesweeper = "1 * .".parse().unwrap();
+        let mut json = vec![];
+        ms.save(&mut json, Format::Json).unwrap();
+        // a 2 next to a single hidden cell can't be right
+        let mut value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        value["grid"]["cells"][0]["surrounds"] = 2.into();
+        let ms = Minesweeper::load(value.to_string().as_bytes(), Format::Json).unwrap();
+
+        assert!(mine_probabilities(&ms).iter().all(Option::is_none));
+        assert_matches_brute_force(&ms);
+    }
+}
@@ -0,0 +1,504 @@
+//! Recorded games that can be played again through the [`Minesweeper`] API.
+//!
+//! A replay is a text file: a header, how the grid is made and every input
+//! with the milliseconds elapsed since the first cell was revealed, inputs
+//! before it are at 0. An input that generated the grid is followed by a
+//! `grid` line and the `mine x y` lines of that grid. Empty lines and lines
+//! starting with `#` are skipped.
+//!
+//! ```text
+//! minesweeper replay 1
+//! width 9
+//! height 9
+//! mines 10
+//! seed 42
+//! generation random
+//! first_click safe_cell
+//! practice 0
+//! 0 open 4 4
+//! grid
+//! mine 0 0
+//! mine 7 0
+//! # ... a line for every mine
+//! 1250 flag 3 4
+//! 2100 chord 4 3
+//! 2500 undo
+//! 2600 redo
+//! 3000 hint
+//! ```
+//!
+//! A generated grid is drawn again from the seed on the first open, so undoing
+//! the first move and opening another cell gives the same grid as in the game.
+//! Playing fails if it differs from the recorded one. A game started from a
+//! fixed layout lists its mines before the inputs instead.
+
+use crate::{
+    grid::Coord,
+    minesweeper::{
+        Action, ConfigError, FirstClickPolicy, GenerationPolicy, Minesweeper, MinesweeperBuilder, MinesweeperError,
+    },
+};
+use std::{
+    error::Error,
+    fmt,
+    io::{self, BufRead, Write},
+    time::Duration,
+};
+
+const HEADER: &str = "minesweeper replay 1";
+
+/// Anything the player can do to a game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    Action(Action),
+    Undo,
+    Redo,
+    /// A safe cell was shown, it does not change the game
+    Hint,
+}
+
+impl Input {
+    pub fn apply(self, ms: &mut Minesweeper) -> Result<(), MinesweeperError> {
+        match self {
+            Input::Action(Action::Open(x, y)) => ms.open(x, y).map(|_| ()),
+            Input::Action(Action::Flag(x, y)) => ms.flag(x, y).map(|_| ()),
+            Input::Action(Action::Chord(x, y)) => ms.chord(x, y).map(|_| ()),
+            Input::Undo => ms.undo().map(|_| ()),
+            Input::Redo => ms.redo().map(|_| ()),
+            Input::Hint => Ok(()),
+        }
+    }
+}
+
+/// An input and when it was made
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// Time since the first revealed cell
+    pub time: Duration,
+    pub input: Input,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// The line with the given number (starting from 1) can't be read
+    Syntax { line: usize },
+    /// The header does not describe a valid game
+    Config(ConfigError),
+    /// The input with the given index can't be made
+    Input { index: usize, error: MinesweeperError },
+    /// The input with the given index generated another grid than the recorded one
+    Layout { index: usize },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "could not read the replay: {}", e),
+            ReplayError::Syntax { line } => write!(f, "invalid replay on line {}", line),
+            ReplayError::Config(e) => write!(f, "invalid replay game: {}", e),
+            ReplayError::Input { index, error } => write!(f, "input {} of the replay failed: {}", index + 1, error),
+            ReplayError::Layout { index } => {
+                write!(f, "input {} of the replay generated another grid than the recorded one", index + 1)
+            }
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReplayError::Io(e) => Some(e),
+            ReplayError::Syntax { .. } | ReplayError::Layout { .. } => None,
+            ReplayError::Config(e) => Some(e),
+            ReplayError::Input { error, .. } => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
+
+impl From<ConfigError> for ReplayError {
+    fn from(e: ConfigError) -> Self {
+        ReplayError::Config(e)
+    }
+}
+
+/// A whole recorded game
+///
+/// ```
+/// use minesweeper::{grid::Coord, minesweeper::{Action, MinesweeperBuilder}, replay::{Input, Replay}};
+/// use std::time::Duration;
+///
+/// let mut ms = MinesweeperBuilder::new(9, 9, 10).seed(7).build().unwrap();
+/// let mut replay = Replay::new(&ms);
+/// replay.record(&mut ms, Input::Action(Action::Open(4, 4)), Duration::ZERO).unwrap();
+/// assert_eq!(replay.generated[0].mines, ms.mine_layout());
+///
+/// let mut file = vec![];
+/// replay.write(&mut file).unwrap();
+/// let replay = Replay::read(&file[..]).unwrap();
+/// assert_eq!(replay.play().unwrap().grid()[Coord::new(4, 4)].revealed, true);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Replay {
+    pub width: usize,
+    pub height: usize,
+    pub number_of_mines: usize,
+    pub seed: u64,
+    pub generation: GenerationPolicy,
+    pub first_click: FirstClickPolicy,
+    pub practice: bool,
+    /// Mines of a game that did not generate its grid, empty otherwise
+    pub mines: Vec<Coord>,
+    pub records: Vec<Record>,
+    /// Every grid generated during the game, in the order of the inputs
+    pub generated: Vec<Generated>,
+}
+
+/// A grid drawn from the seed by an input
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Generated {
+    /// Index of the input in [`Replay::records`]
+    pub input: usize,
+    pub mines: Vec<Coord>,
+}
+
+impl Replay {
+    /// A replay of the game `ms`, made before its first input
+    pub fn new(ms: &Minesweeper) -> Self {
+        Replay {
+            width: ms.width,
+            height: ms.height,
+            number_of_mines: ms.number_of_mines(),
+            seed: ms.seed,
+            generation: ms.generation(),
+            first_click: ms.first_click(),
+            practice: ms.practice(),
+            mines: match ms.mines_placed() {
+                true => ms.mine_layout(),
+                false => vec![],
+            },
+            records: vec![],
+            generated: vec![],
+        }
+    }
+
+    /// Applies `input` to the game and records it, with the grid it generated if any
+    pub fn record(&mut self, ms: &mut Minesweeper, input: Input, time: Duration) -> Result<(), MinesweeperError> {
+        let placed = ms.mines_placed();
+        input.apply(ms)?;
+        if !placed && ms.mines_placed() {
+            self.generated.push(Generated { input: self.records.len(), mines: ms.mine_layout() });
+        }
+        self.records.push(Record { time, input });
+        Ok(())
+    }
+
+    /// Applies the input with the given index to the game, checking the grid it generates
+    pub fn apply(&self, ms: &mut Minesweeper, index: usize) -> Result<(), ReplayError> {
+        let placed = ms.mines_placed();
+        self.records[index].input.apply(ms).map_err(|error| ReplayError::Input { index, error })?;
+        if !placed && ms.mines_placed() {
+            let recorded = self.generated.iter().find(|generated| generated.input == index);
+            if recorded.map_or(true, |generated| generated.mines != ms.mine_layout()) {
+                return Err(ReplayError::Layout { index });
+            }
+        }
+        Ok(())
+    }
+
+    /// The game before any input
+    pub fn game(&self) -> Result<Minesweeper, ConfigError> {
+        let builder = MinesweeperBuilder::new(self.width, self.height, self.number_of_mines)
+            .seed(self.seed)
+            .generation(self.generation)
+            .first_click(self.first_click)
+            .practice(self.practice);
+        match self.mines.is_empty() {
+            true => builder.build(),
+            false => builder.build_with_mines(&self.mines),
+        }
+    }
+
+    /// The game after the first `count` inputs
+    pub fn play_to(&self, count: usize) -> Result<Minesweeper, ReplayError> {
+        let mut ms = self.game()?;
+        for index in 0..count.min(self.records.len()) {
+            self.apply(&mut ms, index)?;
+        }
+        Ok(ms)
+    }
+
+    /// The game after every input
+    pub fn play(&self) -> Result<Minesweeper, ReplayError> {
+        self.play_to(self.records.len())
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "{}", HEADER)?;
+        writeln!(writer, "width {}", self.width)?;
+        writeln!(writer, "height {}", self.height)?;
+        writeln!(writer, "mines {}", self.number_of_mines)?;
+        writeln!(writer, "seed {}", self.seed)?;
+        match self.generation {
+            GenerationPolicy::Random => writeln!(writer, "generation random")?,
+            GenerationPolicy::NoGuess { attempts } => writeln!(writer, "generation no_guess {}", attempts)?,
+        }
+        match self.first_click {
+            FirstClickPolicy::SafeCell => writeln!(writer, "first_click safe_cell")?,
+            FirstClickPolicy::SafeArea => writeln!(writer, "first_click safe_area")?,
+            FirstClickPolicy::Unprotected => writeln!(writer, "first_click unprotected")?,
+        }
+        writeln!(writer, "practice {}", self.practice as u8)?;
+        for c in &self.mines {
+            writeln!(writer, "mine {} {}", c.x, c.y)?;
+        }
+
+        let mut generated = self.generated.iter().peekable();
+        for (index, record) in self.records.iter().enumerate() {
+            write!(writer, "{} ", record.time.as_millis())?;
+            match record.input {
+                Input::Action(Action::Open(x, y)) => writeln!(writer, "open {} {}", x, y)?,
+                Input::Action(Action::Flag(x, y)) => writeln!(writer, "flag {} {}", x, y)?,
+                Input::Action(Action::Chord(x, y)) => writeln!(writer, "chord {} {}", x, y)?,
+                Input::Undo => writeln!(writer, "undo")?,
+                Input::Redo => writeln!(writer, "redo")?,
+                Input::Hint => writeln!(writer, "hint")?,
+            }
+            if let Some(grid) = generated.next_if(|grid| grid.input == index) {
+                writeln!(writer, "grid")?;
+                for c in &grid.mines {
+                    writeln!(writer, "mine {} {}", c.x, c.y)?;
+                }
+            }
+        }
+        writer.flush()
+    }
+
+    pub fn read<R: BufRead>(reader: R) -> Result<Replay, ReplayError> {
+        let mut replay = Replay {
+            width: 0,
+            height: 0,
+            number_of_mines: 0,
+            seed: 0,
+            generation: GenerationPolicy::Random,
+            first_click: FirstClickPolicy::SafeCell,
+            practice: false,
+            mines: vec![],
+            records: vec![],
+            generated: vec![],
+        };
+        let mut header = false;
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let syntax = || ReplayError::Syntax { line: i + 1 };
+            if !header {
+                if line != HEADER {
+                    return Err(syntax());
+                }
+                header = true;
+                continue;
+            }
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let number = |word: usize| words.get(word).and_then(|w| w.parse::<usize>().ok());
+            let cell = |word: usize| number(word).zip(number(word + 1));
+
+            match (words[0], words.len()) {
+                ("width", 2) => replay.width = number(1).ok_or_else(syntax)?,
+                ("height", 2) => replay.height = number(1).ok_or_else(syntax)?,
+                ("mines", 2) => replay.number_of_mines = number(1).ok_or_else(syntax)?,
+                ("seed", 2) => replay.seed = words[1].parse().map_err(|_| syntax())?,
+                ("generation", _) => {
+                    replay.generation = match (words.get(1).copied(), words.len()) {
+                        (Some("random"), 2) => GenerationPolicy::Random,
+                        (Some("no_guess"), 3) => GenerationPolicy::NoGuess { attempts: number(2).ok_or_else(syntax)? },
+                        _ => return Err(syntax()),
+                    }
+                }
+                ("first_click", 2) => {
+                    replay.first_click = match words[1] {
+                        "safe_cell" => FirstClickPolicy::SafeCell,
+                        "safe_area" => FirstClickPolicy::SafeArea,
+                        "unprotected" => FirstClickPolicy::Unprotected,
+                        _ => return Err(syntax()),
+                    }
+                }
+                ("practice", 2) => replay.practice = number(1).ok_or_else(syntax)? != 0,
+                ("grid", 1) => {
+                    let input = replay.records.len().checked_sub(1).ok_or_else(syntax)?;
+                    if replay.generated.last().map_or(false, |grid| grid.input == input) {
+                        return Err(syntax());
+                    }
+                    replay.generated.push(Generated { input, mines: vec![] });
+                }
+                ("mine", 3) => {
+                    let c = cell(1).ok_or_else(syntax)?.into();
+                    // the mines of the last generated grid, or of the fixed one before any input
+                    match replay.generated.last_mut() {
+                        Some(grid) if grid.input + 1 == replay.records.len() => grid.mines.push(c),
+                        None if replay.records.is_empty() => replay.mines.push(c),
+                        _ => return Err(syntax()),
+                    }
+                }
+                _ => {
+                    let time: u64 = words[0].parse().map_err(|_| syntax())?;
+                    let input = match (words.get(1).copied(), words.len()) {
+                        (Some("open"), 4) => cell(2).map(|(x, y)| Input::Action(Action::Open(x, y))),
+                        (Some("flag"), 4) => cell(2).map(|(x, y)| Input::Action(Action::Flag(x, y))),
+                        (Some("chord"), 4) => cell(2).map(|(x, y)| Input::Action(Action::Chord(x, y))),
+                        (Some("undo"), 2) => Some(Input::Undo),
+                        (Some("redo"), 2) => Some(Input::Redo),
+                        (Some("hint"), 2) => Some(Input::Hint),
+                        _ => None,
+                    };
+                    replay.records.push(Record {
+                        time: Duration::from_millis(time),
+                        input: input.ok_or_else(syntax)?,
+                    });
+                }
+            }
+        }
+
+        if !header {
+            return Err(ReplayError::Syntax { line: 1 });
+        }
+        Ok(replay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // plays `inputs` on `ms` like the app does, recording each of them
+    fn record(mut ms: Minesweeper, inputs: &[Input]) -> (Minesweeper, Replay) {
+        let mut replay = Replay::new(&ms);
+        for (i, &input) in inputs.iter().enumerate() {
+            replay.record(&mut ms, input, Duration::from_millis(i as u64 * 100)).unwrap();
+        }
+        (ms, replay)
+    }
+
+    fn read(text: &str) -> Result<Replay, ReplayError> {
+        Replay::read(text.as_bytes())
+    }
+
+    #[test]
+    fn plays_a_written_replay_again() {
+        let ms = MinesweeperBuilder::new(16, 16, 40)
+            .seed(5)
+            .generation(GenerationPolicy::NoGuess { attempts: 10 })
+            .first_click(FirstClickPolicy::SafeArea)
+            .build()
+            .unwrap();
+        let inputs = [
+            Input::Action(Action::Flag(0, 0)),
+            Input::Action(Action::Open(8, 8)),
+            Input::Undo,
+            Input::Redo,
+            Input::Hint,
+            Input::Action(Action::Flag(0, 0)),
+        ];
+        let (ms, replay) = record(ms, &inputs);
+
+        let mut file = vec![];
+        replay.write(&mut file).unwrap();
+        let read = Replay::read(&file[..]).unwrap();
+        assert_eq!(read, replay);
+        assert!(read.mines.is_empty());
+        // the open and its redo
+        assert_eq!(read.generated.iter().map(|grid| grid.input).collect::<Vec<_>>(), [1, 3]);
+
+        let played = read.play().unwrap();
+        assert_eq!(played.mine_layout(), ms.mine_layout());
+        assert_eq!(played.number_of_revealed_cells(), ms.number_of_revealed_cells());
+        assert_eq!(played.number_of_flags(), 0);
+    }
+
+    #[test]
+    fn undone_first_move_is_generated_again() {
+        for seed in 0..100 {
+            let ms = MinesweeperBuilder::new(9, 9, 10).seed(seed).build().unwrap();
+            let inputs = [Input::Action(Action::Open(0, 0)), Input::Undo, Input::Action(Action::Open(8, 8))];
+            let (ms, replay) = record(ms, &inputs);
+
+            assert_eq!(replay.generated.iter().map(|grid| grid.input).collect::<Vec<_>>(), [0, 2]);
+
+            let played = replay.play().unwrap();
+            assert_eq!(played.mine_layout(), ms.mine_layout(), "seed {}", seed);
+            assert_eq!(played.playing, ms.playing);
+        }
+    }
+
+    #[test]
+    fn rejects_another_generated_grid() {
+        let ms = MinesweeperBuilder::new(9, 9, 10).seed(3).build().unwrap();
+        let (_, mut replay) = record(ms, &[Input::Action(Action::Open(4, 4)), Input::Action(Action::Flag(0, 0))]);
+
+        let mut moved = replay.clone();
+        moved.generated[0].mines[0] = Coord::new(4, 4);
+        assert!(matches!(moved.play(), Err(ReplayError::Layout { index: 0 })));
+        assert!(moved.play_to(0).is_ok());
+
+        replay.generated.clear();
+        assert!(matches!(replay.play(), Err(ReplayError::Layout { index: 0 })));
+    }
+
+    #[test]
+    fn keeps_the_mines_of_a_fixed_layout() {
+        let mines = [Coord::new(3, 0), Coord::new(1, 1)];
+        let ms = Minesweeper::from_mine_layout(4, 3, &mines).unwrap();
+        let (ms, replay) = record(ms, &[Input::Action(Action::Open(0, 2))]);
+        assert_eq!(replay.mines, mines);
+
+        let mut file = vec![];
+        replay.write(&mut file).unwrap();
+        let played = Replay::read(&file[..]).unwrap().play().unwrap();
+        assert_eq!(played.number_of_revealed_cells(), ms.number_of_revealed_cells());
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let syntax = |text: &str| match read(text) {
+            Err(ReplayError::Syntax { line }) => Some(line),
+            _ => None,
+        };
+        let header = "minesweeper replay 1\n";
+
+        assert_eq!(syntax(""), Some(1));
+        assert_eq!(syntax("minesweeper replay 2\n"), Some(1));
+        assert_eq!(syntax(&format!("{}width nine\n", header)), Some(2));
+        assert_eq!(syntax(&format!("{}# comment\n\nmine 1\n", header)), Some(4));
+        assert_eq!(syntax(&format!("{}grid\n", header)), Some(2));
+        assert_eq!(syntax(&format!("{}0 open 1 1\ngrid\ngrid\n", header)), Some(4));
+        assert_eq!(syntax(&format!("{}0 open 1 1\nmine 0 0\n", header)), Some(3));
+        assert_eq!(syntax(&format!("{}generation no_guess\n", header)), Some(2));
+        assert_eq!(syntax(&format!("{}first_click anywhere\n", header)), Some(2));
+        assert_eq!(syntax(&format!("{}10 open 1\n", header)), Some(2));
+        assert_eq!(syntax(&format!("{}soon undo\n", header)), Some(2));
+        assert_eq!(syntax(&format!("{}10 jump 1 1\n", header)), Some(2));
+    }
+
+    #[test]
+    fn reports_the_input_that_fails() {
+        let ms = MinesweeperBuilder::new(3, 3, 1).seed(0).build().unwrap();
+        let (_, mut replay) = record(ms, &[Input::Action(Action::Open(1, 1))]);
+        replay.records.push(Record { time: Duration::from_millis(5), input: Input::Action(Action::Open(3, 0)) });
+        assert!(matches!(
+            replay.play(),
+            Err(ReplayError::Input { index: 1, error: MinesweeperError::OutOfBounds { x: 3, y: 0 } })
+        ));
+    }
+}
@@ -0,0 +1,163 @@
+use crate::minesweeper::Minesweeper;
+use std::{
+    error::Error,
+    fmt,
+    io::{Read, Write},
+};
+
+/// How a saved game is encoded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable JSON
+    Json,
+    /// Compact binary encoding
+    Binary,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Json(serde_json::Error),
+    Binary(bincode::Error),
+    /// The loaded grid does not match the saved width, height and counters, or
+    /// the undo history refers to cells outside of it
+    Corrupted,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Json(e) => write!(f, "invalid JSON save: {}", e),
+            SaveError::Binary(e) => write!(f, "invalid binary save: {}", e),
+            SaveError::Corrupted => write!(f, "saved grid does not match its size, counters or history"),
+        }
+    }
+}
+
+impl Error for SaveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SaveError::Json(e) => Some(e),
+            SaveError::Binary(e) => Some(e),
+            SaveError::Corrupted => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        SaveError::Json(e)
+    }
+}
+
+impl From<bincode::Error> for SaveError {
+    fn from(e: bincode::Error) -> Self {
+        SaveError::Binary(e)
+    }
+}
+
+impl Minesweeper {
+    /// Writes the whole game, including the undo history, so it can be resumed exactly
+    pub fn save<W: Write>(&self, writer: W, format: Format) -> Result<(), SaveError> {
+        match format {
+            Format::Json => serde_json::to_writer(writer, self)?,
+            Format::Binary => bincode::serialize_into(writer, self)?,
+        }
+        Ok(())
+    }
+
+    pub fn load<R: Read>(reader: R, format: Format) -> Result<Minesweeper, SaveError> {
+        let ms: Minesweeper = match format {
+            Format::Json => serde_json::from_reader(reader)?,
+            Format::Binary => bincode::deserialize_from(reader)?,
+        };
+
+        let grid = ms.grid();
+        if grid.width() != ms.width || grid.height() != ms.height || grid.len() != ms.width * ms.height {
+            return Err(SaveError::Corrupted);
+        }
+        if !ms.counters_match_grid() || !ms.history_is_on_grid() {
+            return Err(SaveError::Corrupted);
+        }
+        Ok(ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        grid::Coord,
+        minesweeper::{Action, MinesweeperBuilder},
+    };
+
+    fn saved(ms: &Minesweeper, format: Format) -> Vec<u8> {
+        let mut file = vec![];
+        ms.save(&mut file, format).unwrap();
+        file
+    }
+
+    #[test]
+    fn loads_a_game_in_progress() {
+        let mut ms = MinesweeperBuilder::new(9, 9, 10).seed(1).build().unwrap();
+        ms.open(4, 4).unwrap();
+        let mines = ms.mine_layout();
+        let safe: Vec<Coord> = ms.grid().coords().filter(|&c| !ms.grid()[c].revealed && !mines.contains(&c)).collect();
+        ms.flag(mines[0].x, mines[0].y).unwrap();
+        ms.flag(safe[0].x, safe[0].y).unwrap();
+        ms.undo().unwrap();
+
+        for format in [Format::Json, Format::Binary] {
+            let mut loaded = Minesweeper::load(&saved(&ms, format)[..], format).unwrap();
+            assert_eq!(loaded.grid(), ms.grid());
+            assert_eq!(loaded.number_of_revealed_cells(), ms.number_of_revealed_cells());
+            assert_eq!(loaded.number_of_flags(), 1);
+
+            // the history goes on where it was
+            assert_eq!(loaded.redo(), Ok(Some(Action::Flag(safe[0].x, safe[0].y))));
+            assert_eq!(loaded.undo(), Ok(Some(Action::Flag(safe[0].x, safe[0].y))));
+            assert_eq!(loaded.undo(), Ok(Some(Action::Flag(mines[0].x, mines[0].y))));
+            assert_eq!(loaded.number_of_flags(), 0);
+
+            // a win needs the counted flagged mines to be right
+            for &c in &safe {
+                loaded.open(c.x, c.y).unwrap();
+            }
+            for &c in &mines {
+                loaded.flag(c.x, c.y).unwrap();
+            }
+            assert!(!loaded.playing && loaded.won);
+        }
+    }
+
+    #[test]
+    fn rejects_history_outside_of_the_grid() {
+        let mut ms = MinesweeperBuilder::new(4, 4, 2).seed(1).build().unwrap();
+        ms.open(0, 0).unwrap();
+        ms.flag(3, 3).unwrap();
+
+        let mut value: serde_json::Value = serde_json::from_slice(&saved(&ms, Format::Json)).unwrap();
+        value["undo_steps"][1]["action"]["Flag"][0] = 40.into();
+        let error = Minesweeper::load(value.to_string().as_bytes(), Format::Json).err();
+        assert!(matches!(error, Some(SaveError::Corrupted)));
+    }
+
+    #[test]
+    fn rejects_counters_that_do_not_match_the_grid() {
+        let mut ms = MinesweeperBuilder::new(4, 4, 2).seed(1).build().unwrap();
+        ms.open(0, 0).unwrap();
+        ms.flag(3, 3).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&saved(&ms, Format::Json)).unwrap();
+
+        for (counter, count) in [
+            ("number_of_flags", 0),
+            ("number_of_revealed_cells", 15),
+            ("number_of_flagged_mines", 2),
+            ("number_of_mines", 3),
+        ] {
+            let mut value = value.clone();
+            value[counter] = count.into();
+            let error = Minesweeper::load(value.to_string().as_bytes(), Format::Json).err();
+            assert!(matches!(error, Some(SaveError::Corrupted)), "{}", counter);
+        }
+    }
+}
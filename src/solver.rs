@@ -0,0 +1,233 @@
+use crate::{
+    grid::{Coord, Grid},
+    minesweeper::Minesweeper,
+    probability::mine_probabilities,
+};
+
+/// Cells that can be proven from the visible state of a game
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Deductions {
+    pub safe: Vec<Coord>,
+    pub mines: Vec<Coord>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Knowledge {
+    Unknown,
+    Safe,
+    Mine,
+    Revealed(u8),
+}
+
+// a revealed number: `mines` of the `cells` around it hold a mine
+struct Constraint {
+    at: Coord,
+    cells: Vec<Coord>,
+    mines: usize,
+}
+
+/// Finds every hidden cell that is provably safe or provably a mine.
+///
+/// Only revealed numbers are used, flags are treated as hidden cells because
+/// the player may have placed them wrong. Numbers are combined one at a time
+/// and in pairs of overlapping neighbours until nothing new can be proven.
+pub fn solve(ms: &Minesweeper) -> Deductions {
+    let grid = ms.grid();
+    let mut board = Grid::from_fn(ms.width, ms.height, |c| match grid[c].revealed && !grid[c].mine {
+        true => Knowledge::Revealed(grid[c].surrounds),
+        false => Knowledge::Unknown,
+    });
+
+    loop {
+        let constraints = constraints(&board);
+        let mut progress = false;
+
+        for constraint in &constraints {
+            if constraint.mines == 0 {
+                progress |= mark(&mut board, &constraint.cells, Knowledge::Safe);
+            } else if constraint.mines == constraint.cells.len() {
+                progress |= mark(&mut board, &constraint.cells, Knowledge::Mine);
+            }
+        }
+
+        if !progress {
+            progress = overlaps(&mut board, &constraints);
+        }
+
+        if !progress {
+            break;
+        }
+    }
+
+    let mut deductions = Deductions::default();
+    for (c, knowledge) in board.cells() {
+        match knowledge {
+            Knowledge::Safe => deductions.safe.push(c),
+            Knowledge::Mine => deductions.mines.push(c),
+            _ => {}
+        }
+    }
+    deductions
+}
+
+/// A cell worth opening next: a provably safe one if there is any, otherwise
+/// the hidden cell least likely to hold a mine
+pub fn hint(ms: &Minesweeper) -> Option<Coord> {
+    let safe = solve(ms).safe.into_iter().find(|&c| !ms.grid()[c].flag);
+    if safe.is_some() {
+        return safe;
+    }
+
+    let mut best: Option<(Coord, f64)> = None;
+    for (c, probability) in mine_probabilities(ms).cells() {
+        let Some(p) = *probability else {
+            continue;
+        };
+        if ms.grid()[c].flag {
+            continue;
+        }
+        if best.map_or(true, |(_, lowest)| p < lowest) {
+            best = Some((c, p));
+        }
+    }
+    best.map(|(cell, _)| cell)
+}
+
+fn constraints(board: &Grid<Knowledge>) -> Vec<Constraint> {
+    let mut constraints = vec![];
+    for (at, &knowledge) in board.cells() {
+        let Knowledge::Revealed(surrounds) = knowledge else {
+            continue;
+        };
+
+        let mut cells = vec![];
+        let mut known_mines = 0;
+        for n in board.neighbours(at) {
+            match board[n] {
+                Knowledge::Unknown => cells.push(n),
+                Knowledge::Mine => known_mines += 1,
+                _ => {}
+            }
+        }
+
+        // a contradicting board (e.g. hand-made) proves nothing
+        if cells.is_empty() || known_mines > surrounds as usize {
+            continue;
+        }
+        constraints.push(Constraint {
+            at,
+            cells,
+            mines: surrounds as usize - known_mines,
+        });
+    }
+    constraints
+}
+
+// compares every pair of numbers that share hidden cells: if the cells only
+// around `b` must hold all the mines `b` has in excess of `a`, those cells are
+// mines and the cells only around `a` are safe
+fn overlaps(board: &mut Grid<Knowledge>, constraints: &[Constraint]) -> bool {
+    let mut at = Grid::new(board.width(), board.height(), None);
+    for (i, constraint) in constraints.iter().enumerate() {
+        at[constraint.at] = Some(i);
+    }
+
+    let mut progress = false;
+    for a in constraints {
+        // only numbers at most two cells away can share a hidden neighbour
+        for near in board.around(a.at, 2) {
+            let Some(b) = at[near].map(|i| &constraints[i]) else {
+                continue;
+            };
+
+            let only_a: Vec<Coord> = a.cells.iter().filter(|c| !b.cells.contains(c)).copied().collect();
+            let only_b: Vec<Coord> = b.cells.iter().filter(|c| !a.cells.contains(c)).copied().collect();
+            if only_a.len() == a.cells.len() {
+                continue;
+            }
+
+            if b.mines >= a.mines && b.mines - a.mines == only_b.len() {
+                progress |= mark(board, &only_b, Knowledge::Mine);
+                progress |= mark(board, &only_a, Knowledge::Safe);
+            } else if only_a.is_empty() && a.mines == b.mines {
+                progress |= mark(board, &only_b, Knowledge::Safe);
+            }
+        }
+    }
+    progress
+}
+
+fn mark(board: &mut Grid<Knowledge>, cells: &[Coord], knowledge: Knowledge) -> bool {
+    let mut changed = false;
+    for &c in cells {
+        if board[c] == Knowledge::Unknown {
+            board[c] = knowledge;
+            changed = true;
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solved(board: &str) -> Deductions {
+        solve(&board.parse().unwrap())
+    }
+
+    #[test]
+    fn single_numbers() {
+        // the 0 clears its neighbours, which leaves the left 1 a single cell
+        let deductions = solved("
+            * . .
+            1 1 0
+        ");
+        assert_eq!(deductions.safe, vec![Coord::new(1, 0), Coord::new(2, 0)]);
+        assert_eq!(deductions.mines, vec![Coord::new(0, 0)]);
+    }
+
+    #[test]
+    fn overlapping_numbers() {
+        // no number proves anything alone
+        let deductions = solved("
+            * . *
+            1 2 1
+        ");
+        assert_eq!(deductions.safe, vec![Coord::new(1, 0)]);
+        assert_eq!(deductions.mines, vec![Coord::new(0, 0), Coord::new(2, 0)]);
+    }
+
+    #[test]
+    fn flags_are_hidden_cells() {
+        let deductions = solved("
+            F . .
+            1 1 0
+        ");
+        assert_eq!(deductions.mines, vec![Coord::new(0, 0)]);
+
+        // a wrong flag is proven safe
+        let deductions = solved("
+            f * .
+            1 1 1
+        ");
+        assert_eq!(deductions.safe, vec![Coord::new(0, 0), Coord::new(2, 0)]);
+        assert_eq!(deductions.mines, vec![Coord::new(1, 0)]);
+    }
+
+    #[test]
+    fn hint_skips_flags() {
+        let ms: Minesweeper = "
+            f * .
+            1 1 1
+        ".parse().unwrap();
+        assert_eq!(hint(&ms), Some(Coord::new(2, 0)));
+
+        // without a safe cell the least likely mine is picked
+        let ms: Minesweeper = ". *".parse().unwrap();
+        assert_eq!(solve(&ms), Deductions::default());
+        assert_eq!(hint(&ms), Some(Coord::new(0, 0)));
+        let ms: Minesweeper = "f *".parse().unwrap();
+        assert_eq!(hint(&ms), Some(Coord::new(1, 0)));
+    }
+}